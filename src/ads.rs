@@ -0,0 +1,51 @@
+// NTFS alternate data streams, the extra named streams a file can carry next to its
+// normal contents (file.txt:stream). The normal walk never sees them
+use std::ffi::OsString;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+use windows_sys::Win32::Storage::FileSystem::{
+    FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard, WIN32_FIND_STREAM_DATA,
+};
+
+// Paths to every named stream on a file (as file.txt:name), the unnamed main stream is left out.
+// Files on filesystems without streams just come back empty
+pub(crate) fn streams(path: &Path) -> Vec<PathBuf> {
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut data: WIN32_FIND_STREAM_DATA = unsafe { std::mem::zeroed() };
+    let mut found = Vec::new();
+
+    let handle = unsafe {
+        FindFirstStreamW(wide.as_ptr(), FindStreamInfoStandard, &mut data as *mut _ as *mut _, 0)
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return found;
+    }
+
+    loop {
+        if let Some(name) = stream_name(&data.cStreamName) {
+            let mut stream_path = path.as_os_str().to_os_string();
+            stream_path.push(":");
+            stream_path.push(name);
+            found.push(PathBuf::from(stream_path));
+        }
+        if unsafe { FindNextStreamW(handle, &mut data as *mut _ as *mut _) } == 0 {
+            break;
+        }
+    }
+
+    unsafe { FindClose(handle) };
+    found
+}
+
+// Names come back as ":name:$DATA", the main stream is "::$DATA"
+fn stream_name(raw: &[u16]) -> Option<OsString> {
+    let len = raw.iter().position(|&c| c == 0).unwrap_or(raw.len());
+    let name = String::from_utf16_lossy(&raw[..len]);
+    let name = name.strip_prefix(':')?.strip_suffix(":$DATA")?;
+    if name.is_empty() {
+        return None;
+    }
+    Some(OsString::from_wide(&name.encode_utf16().collect::<Vec<_>>()))
+}
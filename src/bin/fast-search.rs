@@ -0,0 +1,201 @@
+// A small grep-style command line front end to the library, for scripts and pipelines:
+//
+//     fast-search --text foo .        path:line:text for every matching line
+//     fast-search -c --text foo .     path:count for every file with a match, like grep -c
+//
+// On a terminal the matches are colored the way grep --color does it, unless NO_COLOR is set.
+// Exits 0 when something matched, 1 when nothing did and 2 when the search failed, the same as grep
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::io::{self, IsTerminal, Write};
+use std::process::ExitCode;
+
+use clap::Parser;
+use fast_search::{FileResult, LineMatch, SearchOptions, SearchOutcome};
+
+// grep's default colors: magenta path, green line number, bold red match
+const PATH_COLOR: &str = "\x1b[35m";
+const LINE_COLOR: &str = "\x1b[32m";
+const MATCH_COLOR: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Parser)]
+#[command(name = "fast-search", about = "Searches a folder for text and/or file names")]
+struct Args {
+    /// The folder (or single file) to search
+    #[arg(default_value = ".")]
+    path: String,
+
+    /// Text to look for in the files
+    #[arg(long)]
+    text: Option<String>,
+
+    /// Text to look for in the file names
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Only print how many lines matched in each file, as path:count
+    #[arg(short = 'c', long)]
+    count: bool,
+
+    /// Case insensitive matching
+    #[arg(short = 'i', long)]
+    ignore_case: bool,
+
+    /// Only take ASCII queries and match them without any Unicode handling, for plain logs
+    #[arg(long)]
+    ascii_only: bool,
+
+    /// Don't search folders deeper than this
+    #[arg(long)]
+    max_depth: Option<usize>,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    if args.text.is_none() && args.name.is_none() {
+        eprintln!("fast-search: nothing to look for, pass --text and/or --name");
+        return ExitCode::from(2);
+    }
+
+    let outcome = match SearchOutcome::search(SearchOptions {
+        root: args.path,
+        text_query: args.text,
+        file_query: args.name,
+        ignore_case: args.ignore_case,
+        ascii_only: args.ascii_only,
+        max_depth: args.max_depth,
+        ..SearchOptions::from_env()
+    }) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            eprintln!("fast-search: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    // files without a matching line aren't listed with -c (grep would say path:0). The walk is
+    // parallel, sorting keeps the output the same from run to run
+    let mut files: Vec<&FileResult> =
+        outcome.files.iter().filter(|f| !f.lines.is_empty() || (f.name_match && !args.count)).collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let color = wants_color(io::stdout().is_terminal(), std::env::var_os("NO_COLOR"));
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let written = write_files(&mut out, &files, args.count, color);
+    // a closed pipe (| head) isn't worth complaining about
+    if let Err(e) = written.and_then(|_| out.flush())
+        && e.kind() != io::ErrorKind::BrokenPipe
+    {
+        eprintln!("fast-search: {}", e);
+        return ExitCode::from(2);
+    }
+
+    if files.is_empty() { ExitCode::from(1) } else { ExitCode::SUCCESS }
+}
+
+// NO_COLOR counts when it's set to anything but an empty string, see no-color.org
+fn wants_color(is_terminal: bool, no_color: Option<OsString>) -> bool {
+    is_terminal && no_color.is_none_or(|v| v.is_empty())
+}
+
+fn write_files(out: &mut impl Write, files: &[&FileResult], count: bool, color: bool) -> io::Result<()> {
+    files.iter().try_for_each(|file| {
+        if count {
+            // grep -c counts lines, a line with several matches is still one
+            let lines: BTreeSet<usize> = file.lines.iter().map(|l| l.line_number).collect();
+            writeln!(out, "{}:{}", painted(&file.path.display().to_string(), PATH_COLOR, color), lines.len())
+        } else if file.lines.is_empty() {
+            writeln!(out, "{}", painted(&file.path.display().to_string(), PATH_COLOR, color))
+        } else {
+            // a line with several matches comes in once per match, they're printed as one line
+            file.lines.chunk_by(|a, b| a.line_number == b.line_number).try_for_each(|matches| {
+                let path = painted(&file.path.display().to_string(), PATH_COLOR, color);
+                let number = painted(&matches[0].line_number.to_string(), LINE_COLOR, color);
+                writeln!(out, "{}:{}:{}", path, number, highlighted(matches, color))
+            })
+        }
+    })
+}
+
+fn painted(text: &str, code: &str, color: bool) -> String {
+    if color { format!("{}{}{}", code, text, RESET) } else { text.to_string() }
+}
+
+// the line with every match on it colored. The columns are byte offsets, a span that doesn't land on
+// a char boundary (a line that wasn't valid UTF-8) is left plain
+fn highlighted(matches: &[LineMatch], color: bool) -> String {
+    let text = &matches[0].line_text;
+    if !color {
+        return text.clone();
+    }
+    let mut spans: Vec<(usize, usize)> =
+        matches.iter().map(|m| (m.match_start_col, m.match_end_col)).collect();
+    spans.sort_unstable();
+
+    let mut line = String::with_capacity(text.len());
+    let mut done = 0;
+    for (start, end) in spans {
+        // overlapping matches get colored as one
+        let start = start.max(done);
+        if end <= start || text.get(done..start).is_none() || text.get(start..end).is_none() {
+            continue;
+        }
+        line.push_str(&text[done..start]);
+        line.push_str(&painted(&text[start..end], MATCH_COLOR, true));
+        done = end;
+    }
+    line.push_str(&text[done..]);
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // what the CLI prints for a search of a folder holding these files
+    fn printed(files: &[(&str, &str)], text: &str, name: Option<&str>, color: bool) -> (String, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+        for (name, contents) in files {
+            std::fs::write(root.join(name), contents).unwrap();
+        }
+        let outcome = SearchOutcome::search(SearchOptions {
+            root: root.display().to_string(),
+            text_query: Some(text.into()),
+            file_query: name.map(Into::into),
+            combine: fast_search::Combine::Or,
+            ..SearchOptions::default()
+        }).unwrap();
+
+        let mut files: Vec<&FileResult> = outcome.files.iter().collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut out = Vec::new();
+        write_files(&mut out, &files, false, color).unwrap();
+        (String::from_utf8(out).unwrap(), root)
+    }
+
+    #[test]
+    fn path_line_text_and_bare_paths() {
+        let (out, root) = printed(&[("a.txt", "none\nhit one, hit two\n"), ("hit.log", "nothing\n")], "hit", Some("hit"), false);
+        let a = root.join("a.txt");
+        let log = root.join("hit.log");
+        assert_eq!(out, format!("{}:2:hit one, hit two\n{}\n", a.display(), log.display()));
+    }
+
+    #[test]
+    fn colors_only_on_a_terminal() {
+        assert!(wants_color(true, None));
+        assert!(wants_color(true, Some("".into())));
+        assert!(!wants_color(true, Some("1".into())));
+        // piped
+        assert!(!wants_color(false, None));
+
+        let (out, root) = printed(&[("a.txt", "say hit\n")], "hit", None, true);
+        let path = root.join("a.txt");
+        assert_eq!(out, format!("\x1b[35m{}\x1b[0m:\x1b[32m1\x1b[0m:say \x1b[1;31mhit\x1b[0m\n", path.display()));
+        let (plain, _) = printed(&[("a.txt", "say hit\n")], "hit", None, false);
+        assert!(!plain.contains('\x1b'));
+    }
+}
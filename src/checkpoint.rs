@@ -0,0 +1,193 @@
+// Remembers which top level parts of the root a search already finished, so a cancelled or crashed
+// search can pick up where it stopped. The file is a header line with a fingerprint of the options
+// and then one finished entry per line: "." for the files directly in the root, otherwise the
+// name of a top level folder. A different fingerprint means different options, those start over
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::SearchOptions;
+
+const HEADER: &str = "fast_search checkpoint v1";
+
+// One slice of the walk: the root's own files, or everything under one of its folders
+pub(crate) enum Pass {
+    Files,
+    Dir(std::ffi::OsString),
+}
+
+impl Pass {
+    fn name(&self) -> String {
+        match self {
+            Pass::Files => ".".to_string(),
+            Pass::Dir(name) => name.to_string_lossy().into_owned(),
+        }
+    }
+
+    // whether an entry from a walk of the whole root belongs to this slice, only the first two
+    // levels decide, anything deeper is under a folder that was already let in
+    pub(crate) fn includes(&self, entry: &ignore::DirEntry) -> bool {
+        let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+        match (entry.depth(), self) {
+            (0, Pass::Files) => true,
+            (0, Pass::Dir(_)) => false,
+            (1, Pass::Files) => !is_dir,
+            (1, Pass::Dir(name)) => is_dir && entry.file_name() == name,
+            _ => true,
+        }
+    }
+}
+
+pub(crate) struct Checkpoint {
+    path: PathBuf,
+    file: File,
+    done: HashSet<String>,
+}
+
+impl Checkpoint {
+    // keeps what an earlier run with the same options finished, anything else gets thrown away
+    pub(crate) fn open(path: &Path, options: &SearchOptions) -> io::Result<Self> {
+        let header = format!("{} {:016x}", HEADER, fingerprint(options));
+        let previous = std::fs::read_to_string(path).unwrap_or_default();
+        let mut lines = previous.lines();
+
+        let done: HashSet<String> = if lines.next() == Some(header.as_str()) {
+            lines.map(str::to_string).collect()
+        } else {
+            HashSet::new()
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if done.is_empty() {
+            file.set_len(0)?;
+            writeln!(file, "{}", header)?;
+        }
+        Ok(Checkpoint { path: path.to_path_buf(), file, done })
+    }
+
+    pub(crate) fn is_done(&self, pass: &Pass) -> bool {
+        self.done.contains(&pass.name())
+    }
+
+    // a folder whose name has a line break in it can't be written down, it just gets searched again
+    pub(crate) fn mark_done(&mut self, pass: &Pass) -> io::Result<()> {
+        let name = pass.name();
+        if name.contains(['\n', '\r']) {
+            return Ok(());
+        }
+        writeln!(self.file, "{}", name)?;
+        self.file.flush()?;
+        self.done.insert(name);
+        Ok(())
+    }
+
+    // the whole search finished, so there's nothing left to resume
+    pub(crate) fn finish(self) -> io::Result<()> {
+        drop(self.file);
+        std::fs::remove_file(&self.path)
+    }
+}
+
+// FNV-1a over every option that changes what a search reports, written out so it stays the same
+// between builds
+fn fingerprint(o: &SearchOptions) -> u64 {
+    let key = format!(
+        "{:?}",
+        (
+            (&o.root, &o.text_query, &o.more_text_queries, o.match_mode, &o.file_query, o.combine, o.ignore_case, o.text_ignore_case, o.file_ignore_case),
+            (o.unicode_case, o.ascii_only, o.regex, o.fixed_string, o.flexible_whitespace, o.scope, o.normalize_unicode, o.detect_encoding, o.max_depth),
+            (&o.file_types, o.file_regex, o.file_glob, o.phonetic, o.match_full_path, o.text_only, o.search_gzip, o.search_all, &o.exclude_dirs),
+            (o.respect_gitignore, o.respect_parent_ignores, o.respect_global_gitignore, o.only_gitignored),
+            (o.hidden_only, o.local_only, o.adaptive_depth, &o.exclude_paths, o.max_entries_per_dir, o.max_file_size, o.dedup_hardlinks, o.follow_symlinks, &o.differs_from),
+            (o.whole_line, o.whole_word, o.line_prefix, o.line_prefix_trim, o.only_matching, o.skip_first_lines, o.context, o.line_range, o.numbering, o.max_line_len),
+            (o.max_matches_per_file, &o.section_marker, o.include_hash, o.include_raw_line, o.blame, o.alternate_streams, o.extractors.extensions()),
+        )
+    );
+    key.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run_search, SearchResult};
+    use std::sync::{Arc, atomic::AtomicBool, mpsc};
+
+    // a tree with a hit in the root's own files and in two folders
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["done", "left"] {
+            std::fs::create_dir(dir.path().join(name)).unwrap();
+            std::fs::write(dir.path().join(name).join("a.txt"), "hit\n").unwrap();
+        }
+        std::fs::write(dir.path().join("top.txt"), "hit\n").unwrap();
+        dir
+    }
+
+    fn options(root: &Path, checkpoint: &Path) -> SearchOptions {
+        SearchOptions {
+            root: root.display().to_string(),
+            text_query: Some("hit".into()),
+            checkpoint: Some(checkpoint.to_path_buf()),
+            ..SearchOptions::default()
+        }
+    }
+
+    // the folders (or "." for the root) each content match came from
+    fn found_in(options: SearchOptions, root: &Path) -> Vec<String> {
+        let (tx, rx) = mpsc::channel();
+        run_search(options, tx, Arc::new(AtomicBool::new(false))).unwrap();
+        let mut found: Vec<_> = rx.into_iter().filter_map(|r| match r {
+            SearchResult::ContentMatch { path, .. } => Some(match path.parent()?.strip_prefix(root).ok()?.to_string_lossy() {
+                dir if dir.is_empty() => ".".to_string(),
+                dir => dir.into_owned(),
+            }),
+            _ => None,
+        }).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn resuming_skips_what_was_finished() {
+        let dir = tree();
+        let state = tempfile::tempdir().unwrap();
+        let file = state.path().join("search.checkpoint");
+        // a run that finished done/ and then got cut off
+        let mut interrupted = Checkpoint::open(&file, &options(dir.path(), &file)).unwrap();
+        interrupted.mark_done(&Pass::Dir("done".into())).unwrap();
+        drop(interrupted);
+
+        assert_eq!(found_in(options(dir.path(), &file), dir.path()), [".", "left"]);
+        // it got all the way through this time
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn other_options_start_over() {
+        let dir = tree();
+        let state = tempfile::tempdir().unwrap();
+        let file = state.path().join("search.checkpoint");
+        let mut interrupted = Checkpoint::open(&file, &options(dir.path(), &file)).unwrap();
+        interrupted.mark_done(&Pass::Dir("done".into())).unwrap();
+        interrupted.mark_done(&Pass::Files).unwrap();
+        drop(interrupted);
+
+        let changed = SearchOptions { ignore_case: true, ..options(dir.path(), &file) };
+        assert_eq!(found_in(changed, dir.path()), [".", "done", "left"]);
+    }
+
+    #[test]
+    fn other_extractors_start_over() {
+        let dir = tree();
+        let state = tempfile::tempdir().unwrap();
+        let file = state.path().join("search.checkpoint");
+        let mut interrupted = Checkpoint::open(&file, &options(dir.path(), &file)).unwrap();
+        interrupted.mark_done(&Pass::Dir("done".into())).unwrap();
+        drop(interrupted);
+
+        let mut extractors = crate::Extractors::empty();
+        extractors.register("log", crate::PlainText);
+        let changed = SearchOptions { extractors, ..options(dir.path(), &file) };
+        assert_eq!(found_in(changed, dir.path()), [".", "done", "left"]);
+    }
+}
@@ -0,0 +1,143 @@
+// Turns files that aren't plain text (documents, archives...) into text the matcher can search,
+// picked by extension. Line numbers in the results are lines of the extracted text, for .docx
+// that's one line per paragraph
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+pub trait Extractor: Send + Sync {
+    // bytes is the whole file as read from disk
+    fn extract<'a>(&self, path: &Path, bytes: &'a [u8]) -> io::Result<Cow<'a, [u8]>>;
+}
+
+// Searches the file as it is, for registering extensions that are really text
+pub struct PlainText;
+
+impl Extractor for PlainText {
+    fn extract<'a>(&self, _path: &Path, bytes: &'a [u8]) -> io::Result<Cow<'a, [u8]>> {
+        Ok(Cow::Borrowed(bytes))
+    }
+}
+
+// Which extractor handles which extension, anything not in here gets searched as plain text
+#[derive(Clone)]
+pub struct Extractors {
+    by_ext: HashMap<String, Arc<dyn Extractor>>,
+}
+
+impl Default for Extractors {
+    // the built-in ones for whichever extractor features are turned on
+    fn default() -> Self {
+        #[cfg_attr(not(feature = "docx"), allow(unused_mut))]
+        let mut extractors = Extractors::empty();
+        #[cfg(feature = "docx")]
+        extractors.register("docx", Docx);
+        extractors
+    }
+}
+
+impl Extractors {
+    pub fn empty() -> Self {
+        Extractors { by_ext: HashMap::new() }
+    }
+
+    // ext without the dot, matched case insensitively. Replaces whatever had it before
+    pub fn register(&mut self, ext: &str, extractor: impl Extractor + 'static) {
+        self.by_ext.insert(ext.to_lowercase(), Arc::new(extractor));
+    }
+
+    // the registered extensions, sorted, for telling two sets apart
+    pub(crate) fn extensions(&self) -> Vec<&str> {
+        let mut exts: Vec<&str> = self.by_ext.keys().map(String::as_str).collect();
+        exts.sort_unstable();
+        exts
+    }
+
+    pub(crate) fn get(&self, path: &Path) -> Option<&dyn Extractor> {
+        if self.by_ext.is_empty() {
+            return None;
+        }
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        self.by_ext.get(&ext).map(|e| e.as_ref())
+    }
+}
+
+// The text of a Word document, read out of the word/document.xml inside the zip
+#[cfg(feature = "docx")]
+pub struct Docx;
+
+#[cfg(feature = "docx")]
+impl Extractor for Docx {
+    fn extract<'a>(&self, _path: &Path, bytes: &'a [u8]) -> io::Result<Cow<'a, [u8]>> {
+        use std::io::Read;
+
+        let mut archive = zip::ZipArchive::new(io::Cursor::new(bytes)).map_err(io::Error::other)?;
+        let mut xml = String::new();
+        archive
+            .by_name("word/document.xml")
+            .map_err(io::Error::other)?
+            .read_to_string(&mut xml)?;
+        Ok(Cow::Owned(docx_text(&xml).into_bytes()))
+    }
+}
+
+// Keeps what's inside <w:t> runs, ends a line at every paragraph and turns tabs/breaks back into text
+#[cfg(feature = "docx")]
+fn docx_text(xml: &str) -> String {
+    let mut out = String::new();
+    let mut rest = xml;
+    let mut in_text = false;
+
+    while let Some(open) = rest.find('<') {
+        if in_text {
+            push_unescaped(&mut out, &rest[..open]);
+        }
+        let Some(close) = rest[open..].find('>') else { break };
+        let tag = &rest[open + 1..open + close];
+        let name = tag.trim_start_matches('/').split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+
+        match name {
+            "w:t" => in_text = !tag.starts_with('/') && !tag.ends_with('/'),
+            "w:p" if tag.starts_with('/') => out.push('\n'),
+            // a <w:tab> with attributes is a tab stop definition, not a tab in the text
+            "w:tab" if tag == "w:tab/" => out.push('\t'),
+            "w:br" | "w:cr" => out.push('\n'),
+            _ => {}
+        }
+        rest = &rest[open + close + 1..];
+    }
+    out
+}
+
+#[cfg(feature = "docx")]
+fn push_unescaped(out: &mut String, text: &str) {
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        let Some(semi) = rest[amp..].find(';') else {
+            out.push_str(&rest[amp..]);
+            return;
+        };
+        let entity = &rest[amp + 1..amp + semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => out.push(c),
+            None => out.push_str(&rest[amp..=amp + semi]),
+        }
+        rest = &rest[amp + semi + 1..];
+    }
+    out.push_str(rest);
+}
@@ -0,0 +1,64 @@
+// Tells hardlinks apart from ordinary files, so dedup_hardlinks can search the shared
+// contents under the first path only, and hashes contents for telling copies of a file apart
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+// 64 bit FNV-1a. Written out here instead of using std's hasher because that one is allowed to
+// change between Rust versions
+pub(crate) struct Fnv(pub(crate) u64);
+
+impl Fnv {
+    pub(crate) fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn feed(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+// a hash of everything in the file, read a chunk at a time so big files don't have to fit in memory
+pub(crate) fn content_hash(path: &Path) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut hash = Fnv::new();
+    let mut chunk = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut chunk)? {
+            0 => return Ok(hash.0),
+            n => hash.feed(&chunk[..n]),
+        }
+    }
+}
+
+// (device, inode) on unix and (volume serial, file index) on windows, the pair is the same
+// for every link to a file. None for files with a single link, those never need checking
+#[cfg(unix)]
+pub(crate) fn hardlink_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = std::fs::metadata(path).ok()?;
+    (meta.nlink() > 1).then(|| (meta.dev(), meta.ino()))
+}
+
+#[cfg(windows)]
+pub(crate) fn hardlink_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{BY_HANDLE_FILE_INFORMATION, GetFileInformationByHandle};
+
+    let file = std::fs::File::open(path).ok()?;
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle() as _, &mut info) } == 0 {
+        return None;
+    }
+    let index = ((info.nFileIndexHigh as u64) << 32) | info.nFileIndexLow as u64;
+    (info.nNumberOfLinks > 1).then_some((info.dwVolumeSerialNumber as u64, index))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn hardlink_id(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
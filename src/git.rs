@@ -0,0 +1,169 @@
+// Asks the git command line about the repo a search root lives in, so there's
+// no need to pull a whole git implementation in as a dependency
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Who last changed a line, from git blame
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlameInfo {
+    pub author: String,
+    // the full hash, all zeros for a line that hasn't been committed yet
+    pub commit: String,
+    // author time in seconds since the unix epoch
+    pub date: i64,
+}
+
+// Every file git tracks under root, None when root isn't inside a repo or git isn't installed
+pub(crate) fn tracked_files(root: &Path) -> Option<Vec<PathBuf>> {
+    let stdout = run_git(root, &["ls-files", "-z", "--cached"])?;
+
+    // run from root, ls-files already limits itself to root and prints paths relative to it
+    Some(
+        stdout
+            .split(|&b| b == 0)
+            .filter(|p| !p.is_empty())
+            .map(|p| root.join(bytes_to_path(p)))
+            .collect(),
+    )
+}
+
+// Files with uncommitted changes under root: modified, added, renamed or untracked.
+// None when root isn't inside a repo or git isn't installed
+pub(crate) fn changed_files(root: &Path) -> Option<Vec<PathBuf>> {
+    // porcelain paths are relative to the repo's top level no matter where git runs from
+    let toplevel = run_git(root, &["rev-parse", "--show-toplevel"])?;
+    let toplevel = bytes_to_path(toplevel.trim_ascii_end());
+
+    let status = run_git(root, &["status", "--porcelain=v1", "-z", "--untracked-files=all", "--", "."])?;
+
+    let mut files = Vec::new();
+    let mut fields = status.split(|&b| b == 0).filter(|f| !f.is_empty());
+    while let Some(field) = fields.next() {
+        if field.len() < 4 {
+            continue;
+        }
+        let (code, path) = field.split_at(3);
+        // renames and copies are followed by the old path which isn't wanted
+        if code[..2].iter().any(|c| matches!(c, b'R' | b'C')) {
+            fields.next();
+        }
+        files.push(toplevel.join(bytes_to_path(path)));
+    }
+    Some(files)
+}
+
+// Blame for every line of the file as it is on disk, index 0 is line 1. One git run for the whole
+// file, None when it isn't in a repo (or git isn't installed)
+pub(crate) fn blame(path: &Path) -> Option<Vec<BlameInfo>> {
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name()?;
+    let output = Command::new("git").arg("-C").arg(dir).args(["blame", "--porcelain", "--"]).arg(name).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    // each line starts with "<hash> <old line> <new line>", the first time a commit shows up its
+    // author lines follow, and then the line itself after a tab
+    let mut commits: HashMap<&[u8], BlameInfo> = HashMap::new();
+    let mut lines = Vec::new();
+    let mut current: Option<&[u8]> = None;
+    for line in output.stdout.split(|&b| b == b'\n') {
+        if line.first() == Some(&b'\t') {
+            if let Some(info) = current.and_then(|hash| commits.get(hash)) {
+                lines.push(info.clone());
+            }
+            continue;
+        }
+        let (key, value) = line.split_at(line.iter().position(|&b| b == b' ').unwrap_or(line.len()));
+        let value = String::from_utf8_lossy(value.strip_prefix(b" ").unwrap_or(value));
+        match key {
+            b"author" => {
+                if let Some(info) = current.and_then(|hash| commits.get_mut(hash)) {
+                    info.author = value.into_owned();
+                }
+            }
+            b"author-time" => {
+                if let Some(info) = current.and_then(|hash| commits.get_mut(hash)) {
+                    info.date = value.trim().parse().unwrap_or(0);
+                }
+            }
+            hash if hash.len() == 40 && hash.iter().all(u8::is_ascii_hexdigit) => {
+                current = Some(hash);
+                commits.entry(hash).or_insert_with(|| BlameInfo {
+                    author: String::new(),
+                    commit: String::from_utf8_lossy(hash).into_owned(),
+                    date: 0,
+                });
+            }
+            _ => {}
+        }
+    }
+    Some(lines)
+}
+
+fn run_git(root: &Path, args: &[&str]) -> Option<Vec<u8>> {
+    let output = Command::new("git").arg("-C").arg(root).args(args).output().ok()?;
+    output.status.success().then_some(output.stdout)
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+// git prints utf-8 paths everywhere else
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // a repo with a.txt and b.txt committed
+    fn repo() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "one\n").unwrap();
+        fs::write(dir.path().join("b.txt"), "two\n").unwrap();
+        git(dir.path(), &["init", "-q"]);
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["-c", "user.name=t", "-c", "user.email=t@t", "commit", "-qm", "start"]);
+        dir
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        assert!(Command::new("git").arg("-C").arg(dir).args(args).status().unwrap().success());
+    }
+
+    fn names(files: Vec<PathBuf>) -> Vec<String> {
+        let mut names: Vec<_> = files.iter().map(|f| f.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn changed_files_are_the_dirty_ones() {
+        let dir = repo();
+        fs::write(dir.path().join("a.txt"), "one, edited\n").unwrap();
+        fs::write(dir.path().join("new.txt"), "").unwrap();
+        fs::write(dir.path().join("staged.txt"), "").unwrap();
+        git(dir.path(), &["add", "staged.txt"]);
+
+        let changed = changed_files(dir.path()).unwrap();
+
+        assert_eq!(names(changed.clone()), ["a.txt", "new.txt", "staged.txt"]);
+        // porcelain paths get joined back onto the top level, so they point at the real files
+        assert!(changed.iter().all(|f| f.is_file()));
+    }
+
+    #[test]
+    fn changed_files_outside_a_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(changed_files(dir.path()), None);
+    }
+}
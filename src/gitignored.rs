@@ -0,0 +1,102 @@
+// Answers "would git ignore this?" one entry at a time, for only_gitignored. The walker's own
+// gitignore filtering just drops entries so that mode turns it off and asks this instead
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+pub(crate) struct IgnoredFilter {
+    // the root as the walker writes it and as an absolute path, the matchers need the absolute one
+    walk_root: PathBuf,
+    root: PathBuf,
+    repo: PathBuf,
+    exclude: Gitignore,
+    global: Gitignore,
+    // each folder's own .gitignore, built the first time something in it is checked
+    files: Mutex<HashMap<PathBuf, Arc<Gitignore>>>,
+    // folders already decided, everything under an ignored folder is ignored too
+    dirs: Mutex<HashMap<PathBuf, bool>>,
+}
+
+impl IgnoredFilter {
+    // None when root isn't inside a git repo, nothing counts as gitignored there
+    pub(crate) fn new(walk_root: &Path, use_global: bool) -> Option<Self> {
+        let root = walk_root.canonicalize().ok()?;
+        let repo = root.ancestors().find(|dir| dir.join(".git").exists())?.to_path_buf();
+
+        let mut exclude = GitignoreBuilder::new(&repo);
+        exclude.add(repo.join(".git").join("info").join("exclude"));
+        let exclude = exclude.build().unwrap_or_else(|_| Gitignore::empty());
+
+        let global = if use_global {
+            GitignoreBuilder::new(&repo).build_global().0
+        } else {
+            Gitignore::empty()
+        };
+
+        Some(IgnoredFilter {
+            walk_root: walk_root.to_path_buf(),
+            root,
+            repo,
+            exclude,
+            global,
+            files: Mutex::new(HashMap::new()),
+            dirs: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // takes a path the way the walker hands it out
+    pub(crate) fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let path = self.root.join(path.strip_prefix(&self.walk_root).unwrap_or(path));
+        self.check(&path, is_dir)
+    }
+
+    fn check(&self, path: &Path, is_dir: bool) -> bool {
+        if let Some(parent) = path.parent()
+            && parent != self.repo
+            && parent.starts_with(&self.repo)
+            && self.dir_ignored(parent)
+        {
+            return true;
+        }
+        self.matched(path, is_dir)
+    }
+
+    fn dir_ignored(&self, dir: &Path) -> bool {
+        if let Some(&known) = self.dirs.lock().unwrap().get(dir) {
+            return known;
+        }
+        let ignored = self.check(dir, true);
+        self.dirs.lock().unwrap().insert(dir.to_path_buf(), ignored);
+        ignored
+    }
+
+    // same order git uses: the closest .gitignore wins, then .git/info/exclude, then the global file
+    fn matched(&self, path: &Path, is_dir: bool) -> bool {
+        let decide = |gitignore: &Gitignore| match gitignore.matched(path, is_dir) {
+            Match::Ignore(_) => Some(true),
+            Match::Whitelist(_) => Some(false),
+            Match::None => None,
+        };
+        for dir in path.ancestors().skip(1).take_while(|dir| dir.starts_with(&self.repo)) {
+            if let Some(ignored) = decide(&self.gitignore(dir)) {
+                return ignored;
+            }
+        }
+        decide(&self.exclude).or_else(|| decide(&self.global)).unwrap_or(false)
+    }
+
+    fn gitignore(&self, dir: &Path) -> Arc<Gitignore> {
+        if let Some(found) = self.files.lock().unwrap().get(dir) {
+            return Arc::clone(found);
+        }
+        // a folder without a .gitignore just gets an empty matcher
+        let mut builder = GitignoreBuilder::new(dir);
+        builder.add(dir.join(".gitignore"));
+        let gitignore = Arc::new(builder.build().unwrap_or_else(|_| Gitignore::empty()));
+        self.files.lock().unwrap().insert(dir.to_path_buf(), Arc::clone(&gitignore));
+        gitignore
+    }
+}
@@ -1,16 +1,49 @@
-use aho_corasick::{AhoCorasick, AhoCorasickBuilder};
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, Input, MatchKind};
 use ignore::WalkState;
-use memchr::{memchr, memrchr};
+use memchr::{memchr, memchr_iter, memrchr};
 use memmap2::Mmap;
+use rayon::prelude::*;
+use regex::RegexBuilder;
+use regex::bytes::Regex;
 use thiserror::Error;
 use std::{
-    collections::HashSet,
+    borrow::Cow,
+    collections::{HashMap, HashSet},
     fs::File,
+    io::Read,
+    ops::Range,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, Condvar, Mutex},
     sync::atomic::{AtomicUsize,AtomicBool, Ordering},
+    time::{Duration, Instant, SystemTime},
 };
 
+mod checkpoint;
+mod extract;
+mod file_id;
+mod git;
+mod gitignored;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+mod outcome;
+mod phonetic;
+mod replace;
+mod results_file;
+mod scope;
+mod volume;
+#[cfg(feature = "serde")]
+mod wire;
+#[cfg(windows)]
+mod ads;
+
+pub use extract::{Extractor, Extractors, PlainText};
+#[cfg(feature = "docx")]
+pub use extract::Docx;
+pub use git::BlameInfo;
+pub use outcome::{search_blocking, FileResult, LineMatch, SearchOutcome};
+pub use replace::{apply_replacements, run_replace, FileReplacement, ReplacedLine};
+pub use scope::MatchScope;
+
 #[derive(Error, Debug)]
 pub enum SearchError {
     #[error(" IO error occurred: {0}")]
@@ -21,196 +54,3710 @@ pub enum SearchError {
 
     #[error("Invalid regex or pattern")]
     PatternError,
+
+    #[error("The query has non-ASCII characters and ascii_only is on")]
+    NonAsciiQuery,
+
+    #[error("The replacement's {0} doesn't name a group in the pattern, braces (${{1}}x) make it clear and $$ is a plain $")]
+    UnclearReplacement(String),
 }
 
+// Option<&'static str>, behind an alias because serde's derive would otherwise only let
+// SearchResult be deserialized from 'static data
+type EncodingName = Option<&'static str>;
+
+// With the serde feature each one (de)serializes as an object tagged with its kind in snake case,
+// {"type":"file_name_match","path":"notes.txt","note":null}
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
 pub enum SearchResult {
     // How you pass content for text matching to the egui
     ContentMatch {
         path: PathBuf,
         line_number: usize,
+        // just the matched text with only_matching
         line_text: String,
+        // line_text got cut short by max_line_len
+        truncated: bool,
+        // which of the query's patterns matched, for telling terms apart
+        pattern: usize,
+        // where the match sits in line_text, in bytes: the offset of its first byte and of the one just
+        // past its last. Always counted from 0 whatever the numbering, so they slice line_text as they
+        // are (with only_matching they're still where the match sat in its line)
+        match_start_col: usize,
+        match_end_col: usize,
+        // the column the match starts at for showing to people, counted the way SearchOptions::numbering
+        // says (from 1 by default)
+        column: usize,
+        // nearest line above the match that looks like the start of a function/section,
+        // only filled in when section_marker is set
+        section: Option<String>,
+        // with include_hash, a hash of the path, line number and line_text that stays the same
+        // between runs and versions, for deduping findings downstream
+        hash: Option<u64>,
+        // with detect_encoding, what the file was decoded from ("UTF-16LE"...) when it had a byte
+        // order mark. None for everything searched as is
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "wire::encoding_name"))]
+        encoding: EncodingName,
+        // with include_raw_line, the bytes of the whole line as they are in the file (after any
+        // decoding), line ending included, so consecutive lines put back together give the exact
+        // text. Empty otherwise
+        raw_line: Vec<u8>,
+        // with blame, who last changed the line. None outside a repo. Boxed so the rarely used
+        // fields don't make every SearchResult bigger
+        blame: Option<Box<BlameInfo>>,
+        // with context, up to that many lines right above and below the match (fewer at the start or
+        // end of the file), without their line endings, top to bottom
+        context_before: Vec<String>,
+        context_after: Vec<String>,
     },
     // How you pass content for file matching to the egui
     FileNameMatch {
         path: PathBuf,
+        // why the name matched when it isn't spelled like the query, with phonetic
+        // which of its words sounded like which ("Steven ~ Stephen (S315)")
+        note: Option<String>,
     },
 
+    // How many entries the walk has visited so far, sent every 50. Threads send them side by side,
+    // so one can come in a little behind the last
+    #[cfg_attr(feature = "serde", serde(rename = "progress", with = "wire::count"))]
     ProgressUpdate(usize),
+
+    // How many entries the walk is going to visit, sent once when count_total's count is done. The
+    // count runs alongside the search, so some ProgressUpdates come before it (and none after, if
+    // the walk finished first)
+    #[cfg_attr(feature = "serde", serde(with = "wire::count"))]
+    ProgressTotal(usize),
+
+    // A directory or file that couldn't be read, only sent with ErrorReporting::PerEntry
+    Error {
+        path: Option<PathBuf>,
+        source: ErrorSource,
+        message: String,
+    },
+
+    // Totals of everything that couldn't be read, sent once after the walk finishes
+    ErrorSummary {
+        dir_errors: usize,
+        file_errors: usize,
+    },
+
+    // A file hit max_matches_per_file, omitted is how many more matches it had
+    MoreMatches {
+        path: PathBuf,
+        omitted: usize,
+    },
+
+    // A folder had more than max_entries_per_dir entries, omitted is how many were left unsearched.
+    // Sent at the end of the walk
+    MoreEntries {
+        path: PathBuf,
+        omitted: usize,
+    },
+
+    // How long one file took to read and scan in microseconds, sent with report_timing
+    FileTiming {
+        path: PathBuf,
+        micros: u64,
+    },
+
+    // A folder (or with the git modes, a file) on a network share or removable drive that local_only left out
+    RemoteSkipped {
+        path: PathBuf,
+    },
+
+    // adaptive_depth found the root on a slow drive and only walks this deep, sent before anything else
+    DepthCapped {
+        root: PathBuf,
+        depth: usize,
+    },
+
+    // max_duration ran out before the search finished, everything sent before it is a partial result
+    TimedOut,
+
+    // the search stopped after max_results matches, sent at the end
+    ResultLimitReached {
+        results: usize,
+    },
+}
+
+// Whether an unreadable entry was a directory (so a whole subtree got skipped) or a single file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ErrorSource {
+    Directory,
+    File,
+}
+
+// How permission/read errors during the walk get reported back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorReporting {
+    // drops them like the walker always used to
+    Silent,
+    // only sends an ErrorSummary with the counts at the end
+    #[default]
+    Summary,
+    // sends an Error for every entry and the ErrorSummary at the end
+    PerEntry,
+}
+
+// Which Unicode normal form the query and file contents are put in before matching, either one
+// makes precomposed "é" and "e" + combining accent match each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    // composed, the form most text is already in
+    Nfc,
+    // decomposed, the form macOS file names use
+    Nfd,
+}
+
+// What a file needs to match when the text query has several terms (text_query plus
+// more_text_queries)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchMode {
+    // a match on any of the terms counts, like one big alternation
+    #[default]
+    Any,
+    // every term has to match somewhere in the file, otherwise nothing from it is reported. Each
+    // match still comes out on its own line with its own pattern: they're held back until the last
+    // missing term shows up, then sent in the order they sit in the file
+    All,
+}
+
+// How ContentMatch counts lines and columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Numbering {
+    // the first line is line 1 and a line's first byte is column 1, like editors show them
+    #[default]
+    OneBased,
+    // both start at 0, like offsets
+    ZeroBased,
+}
+
+impl Numbering {
+    // line n, counting from 1, in this numbering
+    pub fn line(self, n: usize) -> usize {
+        match self {
+            Numbering::OneBased => n,
+            Numbering::ZeroBased => n - 1,
+        }
+    }
+
+    // the byte offset from the start of a line as a column in this numbering
+    pub fn column(self, offset: usize) -> usize {
+        match self {
+            Numbering::OneBased => offset + 1,
+            Numbering::ZeroBased => offset,
+        }
+    }
+}
+
+// How file_query and text_query work together when both are set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Combine {
+    // only the contents of files whose name matched get searched
+    #[default]
+    And,
+    // every file's contents get searched and name matches are reported on their own, so one query
+    // can be put in both to search names and contents at once
+    Or,
 }
 
 // Fields for filtering by and knowing what to look for
 pub struct SearchOptions {
     pub root: String,
     pub text_query: Option<String>,
+    // more text to look for along with text_query, a line with any of them is a match. Each one is
+    // its own pattern in ContentMatch::pattern: 0 is text_query, these are 1, 2... in order
+    pub more_text_queries: Vec<String>,
+    // whether a file needs any or all of the terms, see MatchMode
+    pub match_mode: MatchMode,
     pub file_query: Option<String>,
+    pub combine: Combine,
+    // shorthand for both of the ones below
     pub ignore_case: bool,
-    pub max_depth: usize,
+    // case insensitive content matching only
+    pub text_ignore_case: bool,
+    // keeps only the content matches inside comments (or only those outside them) in source files.
+    // Knows C-style (//, /* */) and # comments for the common languages, files in anything else are all code
+    pub scope: MatchScope,
+    // every run of whitespace in the text query matches any run of spaces/tabs, so "fn main"
+    // finds "fn   main" and "fn\tmain". Goes through the regex engine (which folds case the
+    // Unicode way when ignoring it). Never matches across a line break
+    pub flexible_whitespace: bool,
+    // case insensitive content matching folds case the Unicode way (Ä = ä, Σ = σ = ς, ß = ẞ = ss)
+    // instead of ASCII only, by searching with the regex engine (the query is escaped, so it's still
+    // literal). On plain text that's been about as fast as the aho-corasick matcher, and quicker for
+    // rare words. A query with non-ASCII letters in it always gets this when ignoring case (file
+    // names too), the flag is for ASCII ones that should also fold, e.g. "strasse" finding "STRAẞE"
+    // isn't covered then but "k" finding the Kelvin sign is
+    pub unicode_case: bool,
+    // for corpora known to be ASCII (logs...): a text or file query with anything else in it fails
+    // with SearchError::NonAsciiQuery, and text matching skips everything Unicode. unicode_case is
+    // ignored and a regex's classes (\w, \d, case folding) only know ASCII
+    pub ascii_only: bool,
+    // case insensitive file name matching only
+    pub file_ignore_case: bool,
+    // None walks as deep as the tree goes
+    pub max_depth: Option<usize>,
+    // only searches the first this many entries of each folder (files and subfolders, in the order the
+    // walker lists them), so a folder with a million siblings can't eat the whole search.
+    // The walker still lists all of them, the rest just get skipped
+    pub max_entries_per_dir: Option<usize>,
+    // soft version of max_depth: SearchOutcome sorts its files by relevance, with each folder level
+    // below the root dividing a file's score by another 1 + depth_penalty, so deep files still show
+    // up but after shallow ones that matched as well. run_search leaves results in walk order
+    pub depth_penalty: Option<f32>,
+    // with Combine::Or, a file whose name and contents both matched gets this many extra hits in
+    // SearchOutcome's relevance, so it sorts above files that only matched as often inside. Setting
+    // it ranks the files even without depth_penalty
+    pub name_bonus: Option<f32>,
     pub file_types: Option<String>,
+    // file_query is a regex (e.g. ^test_.*\.rs$) instead of a plain substring
+    pub file_regex: bool,
+    // file_query is a glob (*.rs, test_??.txt, {a,b}.toml) that has to match the whole name instead
+    // of a substring. With match_full_path * stops at a /, ** goes through folders. file_regex wins
+    // when both are set
+    pub file_glob: bool,
+    // file_query matches names that sound like it (soundex, so "Stephen" finds "Steven_cv.pdf") instead
+    // of ones that contain it: each word of the query has to sound like some word of the name.
+    // Case and file_regex don't matter, FileNameMatch::note says which words matched
+    pub phonetic: bool,
+    // file_query is matched against the path below the root ("src/main.rs", always with /) instead of just the name
+    pub match_full_path: bool,
+    pub error_reporting: ErrorReporting,
+    // like grep -F, the query is always taken literally even when a pattern mode is on
+    // (regex is ignored)
+    pub fixed_string: bool,
+    // text_query (and more_text_queries) are regexes, e.g. fn \w+\( or TODO|FIXME, instead of literal
+    // text. One that doesn't compile fails the search with PatternError. flexible_whitespace doesn't
+    // apply to them. ^ and $ match at the start and end of every line, and a match never runs past the
+    // end of its line (\s+ doesn't take the line break)
+    pub regex: bool,
+    // what apply_replacements puts in place of each match of text_query ($1/${name} get filled in
+    // from the match with regex). A search on its own ignores it
+    pub replace: Option<String>,
+    // only looks at files whose first bytes read as text, regardless of extension
+    pub text_only: bool,
+    // a file that records which top level folders are finished, so a search that gets cancelled (or
+    // crashes) skips them when it's run again with the same options. Any option changing starts it
+    // over, and the file is deleted once a search gets all the way through. Not used with the git modes
+    pub checkpoint: Option<PathBuf>,
+    // also appends every result (after the postprocessor, progress left out) to this file as JSON
+    // Lines while the search runs, written out a chunk of whole lines at a time about every second,
+    // so a crash or kill loses at most the last second and never leaves half a line behind. It's
+    // appended to so a run resumed from a checkpoint adds to what the last one wrote. Needs the json feature
+    #[cfg(feature = "json")]
+    pub results_file: Option<PathBuf>,
+    // walks the whole tree first and then searches that fixed list, so a file created while the search
+    // runs is never picked up and one that changes after it was listed gets reported (as an error)
+    // instead of searched half written. Results only start coming once the walk is done
+    pub snapshot: bool,
+    // worker threads for the walk, None lets the walker pick based on the cpu count
+    pub threads: Option<usize>,
+    // soft cap on the bytes of file contents mapped/read at once across all threads, a worker waits
+    // for others to finish before loading a file that would go over. A file bigger than the whole
+    // budget still gets searched, just on its own
+    pub max_loaded_bytes: Option<u64>,
+    // files bigger than this many bytes (a VM image, a database dump...) don't get their contents
+    // searched at all, going by the size on disk (a .gz by its compressed size, a file in /proc that
+    // says it's empty by what it reads). Their names still get matched
+    pub max_file_size: Option<u64>,
+    // stops sending content matches for a file after this many, so one huge file can't drown out the rest
+    pub max_matches_per_file: Option<usize>,
+    // .gitignore files and .git/info/exclude, turning this off skips every .gitignore
+    // including the ones above the root
+    pub respect_gitignore: bool,
+    // also reads ignore files from the directories above the root (up to the repo root)
+    pub respect_parent_ignores: bool,
+    // the user's global gitignore (core.excludesFile)
+    pub respect_global_gitignore: bool,
+    // like git grep, only searches the files git tracks when the root is inside a repo
+    pub git_tracked_only: bool,
+    // only searches files with uncommitted changes (modified, added or untracked), for checking work in progress
+    pub git_changed_only: bool,
+    // the opposite of respect_gitignore, only searches what the repo's gitignore rules leave out
+    // (build output, caches...) for auditing it. Finds nothing outside a repo
+    pub only_gitignored: bool,
+    // turns off the skip list (exclude_dirs and /proc, /sys, /dev) and looks everywhere
+    pub search_all: bool,
+    // names of folders the walk doesn't go into wherever they show up, DEFAULT_EXCLUDE_DIRS to start
+    // with. A name ending in * skips every name starting with the rest. Matched exactly, case included
+    pub exclude_dirs: Vec<String>,
+    // windows only: also searches each file's NTFS alternate data streams, does nothing elsewhere
+    pub alternate_streams: bool,
+    // skips anything on a network share or removable drive so a slow mount can't stall the search.
+    // Detected from the mount table on linux and the drive type on windows, elsewhere nothing gets skipped
+    pub local_only: bool,
+    // with max_depth unset, a root on a network share or removable drive (found the same way as
+    // local_only) is only walked ADAPTIVE_MAX_DEPTH folders deep, and DepthCapped says so.
+    // A max_depth that's set always wins
+    pub adaptive_depth: bool,
+    // files or folders the walk never goes into, e.g. a report being written inside the tree
+    // that's searched. They don't have to exist yet
+    pub exclude_paths: Vec<PathBuf>,
+    // the opposite of the hidden filter, only searches dotfiles (and files marked hidden on windows)
+    // plus whatever is inside hidden folders, e.g. for finding secrets in .env or .npmrc
+    pub hidden_only: bool,
+    // searches the contents behind several hardlinks once, matches are reported under the first path found
+    pub dedup_hardlinks: bool,
+    // walks into symlinked folders (and searches symlinked files) instead of skipping them. A link
+    // back up into a folder it's already inside is caught by the walker and reported as an error
+    // for that path, it doesn't loop
+    pub follow_symlinks: bool,
+    // audit mode for finding copies of a file that drifted apart (a config, a vendored header...): only
+    // files named like this one and with different contents match the file name, each reported as a
+    // FileNameMatch with a note. It takes the place of file_query, and ignore_case/file_ignore_case
+    // apply to the name. A reference that can't be read fails the search
+    pub differs_from: Option<PathBuf>,
+    // decompresses .gz files in memory and searches what's inside
+    pub search_gzip: bool,
+    // longest line_text sent back in bytes, anything longer is cut and flagged as truncated
+    pub max_line_len: Option<usize>,
+    // like grep -x, a line only matches when it is the query and nothing else (surrounding whitespace aside)
+    pub whole_line: bool,
+    // like grep -w, a match has to have a non-word character or the start/end of the line on both
+    // sides, so "int" doesn't match in "point". Word characters are letters, digits, _ and anything
+    // non-ASCII
+    pub whole_word: bool,
+    // only matches at the very start of a line, e.g. lines beginning with "import"
+    pub line_prefix: bool,
+    // with line_prefix, lets indentation come before the match
+    pub line_prefix_trim: bool,
+    // like grep -o, line_text is only the matched text instead of the whole line
+    pub only_matching: bool,
+    // skips a known header, matches in the first this many lines of each file are left out.
+    // Line numbers still count from the top of the file
+    pub skip_first_lines: usize,
+    // like grep -C, this many lines above and below each match come with it in ContentMatch's
+    // context_before/context_after. They're cut at max_line_len like the line itself, 0 leaves them off
+    pub context: usize,
+    // only keeps matches on lines first..=last (counted from 1, both ends included), for looking at
+    // the same region of every file. Line numbers in the results stay counted from the top
+    pub line_range: Option<(usize, usize)>,
+    // whether line_number and the columns of a ContentMatch count from 1 (the default) or 0.
+    // line_range and skip_first_lines count from 1 either way
+    pub numbering: Numbering,
+    // stops the search once it has run this long and sends TimedOut, for time boxed scans in CI
+    pub max_duration: Option<Duration>,
+    // stops the search after this many matches (content and file name ones) and sends
+    // ResultLimitReached. Threads that were mid file finish it, but nothing past the limit gets sent
+    pub max_results: Option<usize>,
+    // regex for lines that start a section (a function, class...), each match gets the nearest one
+    // above it as its section. DEFAULT_SECTION_MARKER covers most code, None leaves section off
+    pub section_marker: Option<String>,
+    // fills in ContentMatch::hash
+    pub include_hash: bool,
+    // fills in ContentMatch::raw_line
+    pub include_raw_line: bool,
+    // fills in ContentMatch::blame by running git blame on every file with a match (once per file).
+    // Slow, a git process per file. Files searched through an extractor or inside .gz are left out
+    // since their line numbers aren't the file's
+    pub blame: bool,
+    // sends a FileTiming for every file whose content took at least this long to open and scan,
+    // for finding the few files that slow a search down. Duration::ZERO times all of them
+    pub report_timing: Option<Duration>,
+    // counts the entries alongside the search so ProgressTotal can go out, for showing a
+    // percentage. The count doesn't hold the search up, and isn't done with checkpoint (earlier
+    // runs' folders get skipped)
+    pub count_total: bool,
+    // decodes files that start with a byte order mark (UTF-8, UTF-16LE/BE) before searching them,
+    // files without one are searched as is. ContentMatch::encoding says which it found
+    pub detect_encoding: bool,
+    // normalizes the text query and every file's contents before matching. Off by default since
+    // each non-ASCII file gets decoded as UTF-8 and copied, lines and columns are of the normalized text
+    pub normalize_unicode: Option<Normalization>,
+    // per extension converters to text (.docx with the docx feature), register your own for other formats
+    pub extractors: Extractors,
+    // set with with_postprocessor
+    pub postprocessor: Option<Postprocessor>,
+}
+
+// Runs on every result before it's sent and can change it or drop it (None), see with_postprocessor
+pub type Postprocessor = Arc<dyn Fn(SearchResult) -> Option<SearchResult> + Send + Sync>;
+
+// How deep adaptive_depth lets a walk of a slow root go
+pub const ADAPTIVE_MAX_DEPTH: usize = 4;
+
+// what the groups around each of several regex terms are named, followed by the term's index
+const TERM_GROUP: &str = "fast_search_term";
+
+// Heavy tool and system folders plus the recycle bins and trash folders that make whole-drive scans
+// crawl, what exclude_dirs starts out as
+pub const DEFAULT_EXCLUDE_DIRS: &[&str] = &[
+    "Windows",
+    "Program Files",
+    "Program Files (x86)",
+    "AppData",
+    "Temp",
+    ".git",
+    "node_modules",
+    "$RECYCLE.BIN",
+    "System Volume Information",
+    ".Trash*",
+];
+
+// fn/def/class lines, or anything that opens a block
+pub const DEFAULT_SECTION_MARKER: &str = r"^\s*(fn|def|class)\b|\{\s*$";
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            root: ".".to_string(),
+            text_query: None,
+            more_text_queries: Vec::new(),
+            match_mode: MatchMode::default(),
+            file_query: None,
+            combine: Combine::default(),
+            ignore_case: false,
+            text_ignore_case: false,
+            unicode_case: false,
+            ascii_only: false,
+            scope: MatchScope::default(),
+            flexible_whitespace: false,
+            file_ignore_case: false,
+            max_depth: None,
+            depth_penalty: None,
+            name_bonus: None,
+            max_entries_per_dir: None,
+            file_types: None,
+            file_regex: false,
+            file_glob: false,
+            phonetic: false,
+            match_full_path: false,
+            error_reporting: ErrorReporting::default(),
+            fixed_string: false,
+            regex: false,
+            replace: None,
+            text_only: false,
+            checkpoint: None,
+            #[cfg(feature = "json")]
+            results_file: None,
+            snapshot: false,
+            threads: None,
+            max_loaded_bytes: None,
+            max_file_size: None,
+            max_matches_per_file: None,
+            respect_gitignore: true,
+            respect_parent_ignores: true,
+            respect_global_gitignore: true,
+            git_tracked_only: false,
+            git_changed_only: false,
+            only_gitignored: false,
+            search_all: false,
+            exclude_dirs: DEFAULT_EXCLUDE_DIRS.iter().map(|name| name.to_string()).collect(),
+            alternate_streams: false,
+            local_only: false,
+            adaptive_depth: false,
+            exclude_paths: Vec::new(),
+            hidden_only: false,
+            dedup_hardlinks: false,
+            follow_symlinks: false,
+            differs_from: None,
+            search_gzip: false,
+            max_line_len: None,
+            whole_line: false,
+            whole_word: false,
+            line_prefix: false,
+            line_prefix_trim: false,
+            only_matching: false,
+            skip_first_lines: 0,
+            context: 0,
+            line_range: None,
+            numbering: Numbering::default(),
+            max_duration: None,
+            max_results: None,
+            section_marker: None,
+            include_hash: false,
+            include_raw_line: false,
+            blame: false,
+            report_timing: None,
+            count_total: false,
+            detect_encoding: false,
+            normalize_unicode: None,
+            extractors: Extractors::default(),
+            postprocessor: None,
+        }
+    }
+}
+
+impl SearchOptions {
+    // Built-in defaults with any FASTSEARCH_* environment variables applied on top, so CI
+    // can configure a search without long command lines. Explicit values win over both:
+    //     SearchOptions { root, text_query, ..SearchOptions::from_env() }
+    //
+    // FASTSEARCH_THREADS, FASTSEARCH_MAX_DEPTH      numbers
+    // FASTSEARCH_FILE_TYPES                         comma separated extensions
+    // FASTSEARCH_IGNORE_CASE, FASTSEARCH_TEXT_ONLY  1/0, true/false, yes/no
+    // FASTSEARCH_ERRORS                             silent, summary or each
+    //
+    // Values that don't parse are skipped and the built-in default stays
+    pub fn from_env() -> Self {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let mut options = SearchOptions::default();
+
+        if let Some(threads) = var("FASTSEARCH_THREADS").and_then(|v| v.trim().parse().ok()) {
+            options.threads = Some(threads);
+        }
+        if let Some(depth) = var("FASTSEARCH_MAX_DEPTH").and_then(|v| v.trim().parse().ok()) {
+            options.max_depth = Some(depth);
+        }
+        if let Some(types) = var("FASTSEARCH_FILE_TYPES").filter(|v| !v.trim().is_empty()) {
+            options.file_types = Some(types);
+        }
+        if let Some(ignore_case) = var("FASTSEARCH_IGNORE_CASE").and_then(|v| parse_flag(&v)) {
+            options.ignore_case = ignore_case;
+        }
+        if let Some(text_only) = var("FASTSEARCH_TEXT_ONLY").and_then(|v| parse_flag(&v)) {
+            options.text_only = text_only;
+        }
+        if let Some(reporting) = var("FASTSEARCH_ERRORS") {
+            match reporting.trim().to_lowercase().as_str() {
+                "silent" => options.error_reporting = ErrorReporting::Silent,
+                "summary" => options.error_reporting = ErrorReporting::Summary,
+                "each" => options.error_reporting = ErrorReporting::PerEntry,
+                _ => {}
+            }
+        }
+
+        options
+    }
+
+    // Passes every result (matches, progress, errors, TimedOut...) through f before it reaches the
+    // channel/writer, for redacting text, rewriting paths or dropping what a rule says to, e.g.
+    //     options.with_postprocessor(|r| match r {
+    //         SearchResult::FileNameMatch { .. } => None,
+    //         other => Some(other),
+    //     })
+    // f gets called from all the walker threads at once, hence Send + Sync, and should be quick since
+    // the worker waits on it. count_matches counts what's left after it, the files_* totals don't change
+    pub fn with_postprocessor(mut self, f: impl Fn(SearchResult) -> Option<SearchResult> + Send + Sync + 'static) -> Self {
+        self.postprocessor = Some(Arc::new(f));
+        self
+    }
+}
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
 }
 
 // Provides a search engine for the matchers and a set of strings for acceptable files
 struct SearchConfig {
-    text_matcher: Option<AhoCorasick>,
-    file_matcher: Option<AhoCorasick>,
+    text_matcher: Option<TextMatcher>,
+    file_matcher: Option<NameMatcher>,
+    reference: Option<Reference>,
     allowed_exts: Option<HashSet<String>>,
+    section_marker: Option<Regex>,
+    options: SearchOptions,
+    // when max_duration runs out, set once it has
+    deadline: Option<Instant>,
+    timed_out: AtomicBool,
+    // exclude_paths and the root, made absolute so they compare the same however they were written
+    excluded: Vec<PathBuf>,
+    absolute_root: Option<PathBuf>,
+    // the mount table for local_only and adaptive_depth, None when they're both off
+    volumes: Option<volume::Volumes>,
+    // the depth adaptive_depth put on the walk, already in options.max_depth
+    depth_capped: Option<usize>,
+    // hardlinked files already searched, by file_id::hardlink_id
+    seen_links: Mutex<HashSet<(u64, u64)>>,
+    // entries seen so far in each folder, for max_entries_per_dir
+    dir_entries: Mutex<HashMap<PathBuf, usize>>,
+    // bytes of file contents loaded right now, for max_loaded_bytes
+    loaded: Mutex<u64>,
+    unloaded: Condvar,
 }
 
-pub fn run_search(options: SearchOptions, tx: std::sync::mpsc::Sender<SearchResult>, thread_token: Arc<AtomicBool>) {
-    // collects the text from SearchOptions and attaches its engine for matching
-    let text_matcher = options.text_query.map(|t| {
-        AhoCorasickBuilder::new()
-            .ascii_case_insensitive(options.ignore_case)
-            .build([t])
-            .expect("Failed to build text matcher")
-    });
+// Holds a file's share of max_loaded_bytes until its contents are dropped
+struct Loan<'a> {
+    config: &'a SearchConfig,
+    bytes: u64,
+}
 
-    // collects the file name and attaches its engine for matching
-    let file_matcher = options.file_query.map(|f| {
-        AhoCorasickBuilder::new()
-            .ascii_case_insensitive(options.ignore_case)
-            .build([f])
-            .expect("Failed to build file matcher")
-    });
+impl Drop for Loan<'_> {
+    fn drop(&mut self) {
+        *self.config.loaded.lock().unwrap() -= self.bytes;
+        self.config.unloaded.notify_all();
+    }
+}
 
-    // collects all file_types and separates them for filtering during actual searching
-    let allowed_exts = options.file_types.map(|s| {
-        s.split(',').map(|ext| ext.trim().to_lowercase()).collect::<HashSet<_>>()
-    });
+// The file name query, taken literally unless file_regex or phonetic is on
+enum NameMatcher {
+    Literal(AhoCorasick),
+    Regex(regex::Regex),
+    // globset only ignores ASCII case, so with fold the glob and the name are both lowercased first
+    Glob { glob: globset::GlobMatcher, fold: bool },
+    // the query's words and their soundex codes
+    Phonetic(Vec<(String, String)>),
+}
 
-    // passes the data to a thread
-    let config = Arc::new(SearchConfig {
-        text_matcher,
-        file_matcher,
-        allowed_exts,
-    });
+impl NameMatcher {
+    // Some when the name matches, with the note for FileNameMatch
+    fn find(&self, name: &str) -> Option<Option<String>> {
+        match self {
+            NameMatcher::Literal(ac) => ac.is_match(name).then_some(None),
+            NameMatcher::Regex(re) => re.is_match(name).then_some(None),
+            NameMatcher::Glob { glob, fold: false } => glob.is_match(name).then_some(None),
+            NameMatcher::Glob { glob, fold: true } => glob.is_match(name.to_lowercase()).then_some(None),
+            // every word of the query has to sound like one of the name's
+            NameMatcher::Phonetic(query) => {
+                let words = phonetic::word_codes(name);
+                let pairs: Option<Vec<String>> = query
+                    .iter()
+                    .map(|(wanted, code)| {
+                        let (word, _) = words.iter().find(|(_, c)| c == code)?;
+                        Some(format!("{} ~ {} ({})", word, wanted, code))
+                    })
+                    .collect();
+                pairs.map(|pairs| Some(pairs.join(", ")))
+            }
+        }
+    }
+}
 
-    
-    // Sets up walking through directories starting from the farthest entered
-    let mut walker = ignore::WalkBuilder::new(&options.root)
-    .max_depth(Some(options.max_depth))
-    .hidden(false)
-    .git_ignore(true)
-    .build_parallel();
-
-    if cfg!(windows) {
-        walker = ignore::WalkBuilder::new(&options.root)
-        .max_depth(Some(options.max_depth))
-        .hidden(true)
-        .git_ignore(true)
-        // allows multiple to run by splitting them across threads
-        .build_parallel();
-    } 
-    
-    let scanned_count = Arc::new(AtomicUsize::new(0));
+// The differs_from file, what a same-named file gets compared against
+struct Reference {
+    path: PathBuf,
+    name: String,
+    ignore_case: bool,
+    len: u64,
+    hash: u64,
+}
 
-    // Begins walking through directories
-    walker.run(|| {
-        let conf = Arc::clone(&config);
-        let tx = &tx;
-        let count = Arc::clone(&scanned_count);
-        let cancel_status = &thread_token;
+impl Reference {
+    fn load(path: &Path, ignore_case: bool) -> std::io::Result<Self> {
+        Ok(Reference {
+            path: path.to_path_buf(),
+            name: path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            ignore_case,
+            len: std::fs::metadata(path)?.len(),
+            hash: file_id::content_hash(path)?,
+        })
+    }
 
-        // files/directories data being accessed
-        Box::new(move |result| {
-            let current_val = count.fetch_add(1, Ordering::Relaxed);
-            if (current_val + 1) % 50 == 0 {
-                let _ = tx.send(SearchResult::ProgressUpdate(50));
+    // Some with the note for FileNameMatch when path is named the same but has other contents. A
+    // different size settles it before anything gets read, a file that can't be read doesn't match
+    fn differs(&self, path: &Path) -> Option<Option<String>> {
+        let name = path.file_name()?.to_string_lossy();
+        let same_name = if self.ignore_case { name.eq_ignore_ascii_case(&self.name) } else { name == self.name };
+        if !same_name {
+            return None;
+        }
+        let len = std::fs::metadata(path).ok()?.len();
+        let differs = len != self.len || file_id::content_hash(path).ok()? != self.hash;
+        differs.then(|| Some(format!("differs from {}", self.path.display())))
+    }
+}
+
+// The text query, aho-corasick unless it needs something only the regex engine does
+pub(crate) enum TextMatcher {
+    Literal(AhoCorasick),
+    // with several terms, the capture group each of them is wrapped in, by pattern index
+    Regex { re: Regex, term_groups: Vec<usize> },
+}
+
+impl TextMatcher {
+    // collects the text from SearchOptions and attaches its engine for matching, None without a text query.
+    // run_replace builds its matcher here too so it replaces exactly what the search found
+    pub(crate) fn new(options: &SearchOptions) -> Result<Option<Self>, SearchError> {
+        options.text_query.as_ref().map(|t| {
+            let terms: Vec<Cow<[u8]>> = std::iter::once(t)
+                .chain(&options.more_text_queries)
+                .map(|t| match options.normalize_unicode {
+                    Some(form) => normalize(t.as_bytes(), form),
+                    None => Cow::Borrowed(t.as_bytes()),
+                })
+                .collect();
+            let ignore_case = options.ignore_case || options.text_ignore_case;
+            let as_regex = options.regex && !options.fixed_string;
+            let unicode_case = (options.unicode_case || terms.iter().any(|t| !t.is_ascii())) && !options.ascii_only;
+            if as_regex || (ignore_case && unicode_case) || options.flexible_whitespace {
+                // escaped so the query is still taken literally unless it's meant as a regex. With
+                // several terms each one is a named group of its own, find_iter goes by which matched
+                let patterns: Vec<String> = terms.iter().map(|t| {
+                    let t = String::from_utf8_lossy(t);
+                    if as_regex {
+                        t.into_owned()
+                    } else if options.flexible_whitespace {
+                        flexible_whitespace(&t)
+                    } else if ignore_case {
+                        escape_folding(&t)
+                    } else {
+                        regex::escape(&t)
+                    }
+                }).collect();
+                let pattern = match patterns.as_slice() {
+                    [one] => one.clone(),
+                    _ => patterns.iter().enumerate().map(|(i, t)| format!("(?P<{}{}>{})", TERM_GROUP, i, t)).collect::<Vec<_>>().join("|"),
+                };
+                // the whole file is one haystack, multi_line makes ^ and $ match at every line (crlf
+                // so $ also does before a \r\n)
+                let re = regex::bytes::RegexBuilder::new(&pattern)
+                    .case_insensitive(ignore_case)
+                    .multi_line(true)
+                    .crlf(true)
+                    .unicode(!options.ascii_only)
+                    .build()
+                    .map_err(|_| SearchError::PatternError)?;
+                let term_groups = match patterns.len() {
+                    1 => Vec::new(),
+                    n => (0..n)
+                        .filter_map(|i| re.capture_names().position(|name| name == Some(&format!("{}{}", TERM_GROUP, i))))
+                        .collect(),
+                };
+                Ok(TextMatcher::Regex { re, term_groups })
+            } else {
+                // leftmost-first is how the regex alternation picks between terms, so a term that's
+                // the start of another one finds the same spans either way
+                AhoCorasickBuilder::new()
+                    .match_kind(MatchKind::LeftmostFirst)
+                    .ascii_case_insensitive(ignore_case)
+                    .build(&terms)
+                    .map(TextMatcher::Literal)
+                    .map_err(|_| SearchError::PatternError)
             }
-            if cancel_status.load(Ordering::Relaxed) {
-                return WalkState::Quit;
+        }).transpose()
+    }
+
+    // which pattern matched and the byte range of every match in haystack from start on. Both engines
+    // give offsets into the same bytes, so columns and highlighting come out the same whichever one ran
+    // (a regex of one term is pattern 0, with several the term's group that matched says which)
+    // Empty matches (a*, an empty query) are only kept where a line's text can be cut: not inside a
+    // multi-byte character, and not at the very end of a file that ends in a line break, there's no
+    // line there
+    pub(crate) fn find_iter<'a>(&'a self, haystack: &'a [u8], start: usize) -> Box<dyn Iterator<Item = (usize, Range<usize>)> + 'a> {
+        let matches: Box<dyn Iterator<Item = (usize, Range<usize>)> + 'a> = match self {
+            TextMatcher::Literal(ac) => Box::new(
+                ac.find_iter(Input::new(haystack).range(start..)).map(|m| (m.pattern().as_usize(), m.range())),
+            ),
+            TextMatcher::Regex { re, term_groups } => {
+                Box::new(LineMatches { re, term_groups, haystack, pos: start, line_end: None, last_end: None })
             }
+        };
+        Box::new(matches.filter(move |(_, m)| !m.is_empty() || is_empty_match_spot(haystack, m.start)))
+    }
+}
 
-            // handles issues with permissions blocking entry
-            let entry = match result {
-                Ok(e) => e,
-                Err(_) => return WalkState::Continue,
+// A regex's matches in haystack from pos on, none of them running over a line break. One that
+// would (c\s+x over "c\nx") gets looked for again on its own line only, so a shorter match that fits
+// on the line still turns up, and the search carries on from the next line
+struct LineMatches<'a> {
+    re: &'a Regex,
+    term_groups: &'a [usize],
+    haystack: &'a [u8],
+    pos: usize,
+    // where the line being searched on its own ends
+    line_end: Option<usize>,
+    // where the last match ended, an empty match right there isn't another one (the same as find_iter)
+    last_end: Option<usize>,
+}
+
+impl Iterator for LineMatches<'_> {
+    type Item = (usize, Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let haystack = &self.haystack[..self.line_end.unwrap_or(self.haystack.len())];
+            let found = if self.pos > haystack.len() {
+                None
+            } else if self.term_groups.is_empty() {
+                self.re.find_at(haystack, self.pos).map(|m| (0, m.range()))
+            } else {
+                self.re.captures_at(haystack, self.pos).map(|caps| {
+                    let pattern = self.term_groups.iter().position(|&g| caps.get(g).is_some()).unwrap_or(0);
+                    (pattern, caps.get_match().range())
+                })
             };
 
-            // Skips over most files with permission issues/massive sizes
-            if entry.depth() > 0 && !is_important(&entry) {
-                return WalkState::Skip;
+            let Some((pattern, range)) = found else {
+                match self.line_end.take() {
+                    Some(end) => {
+                        self.pos = end + 1;
+                        continue;
+                    }
+                    None => return None,
+                }
+            };
+            if self.line_end.is_none()
+                && let Some(newline) = memchr(b'\n', &haystack[range.clone()])
+            {
+                self.line_end = Some(range.start + newline);
+                self.pos = range.start;
+                continue;
+            }
+            // an empty match has to move on by hand or it would be found again
+            self.pos = if range.is_empty() { range.end + 1 } else { range.end };
+            if range.is_empty() && self.last_end == Some(range.start) {
+                continue;
             }
+            self.last_end = Some(range.end);
+            return Some((pattern, range));
+        }
+    }
+}
+
+// true where an empty match can sit: on a char boundary of the line, and before the end of a file
+// only if the last line has no line break after it
+fn is_empty_match_spot(haystack: &[u8], at: usize) -> bool {
+    match haystack.get(at) {
+        Some(&b) => (b & 0b1100_0000) != 0b1000_0000,
+        None => !haystack.is_empty() && !haystack.ends_with(b"\n"),
+    }
+}
+
+// Totals from count_matches, matches counts every content hit plus every file name hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MatchCounts {
+    pub files_scanned: usize,
+    pub files_matched: usize,
+    pub total_matches: usize,
+    // folders/files local_only left out for being on a network share or removable drive
+    pub remote_skipped: usize,
+}
+
+// One matched line as process_file_content found it, before it's turned into a ContentMatch
+struct LineHit<'a> {
+    line_number: usize,
+    line: &'a [u8],
+    truncated: bool,
+    pattern: usize,
+    // the match relative to the start of line, in bytes from 0
+    span: (usize, usize),
+    // span.0 in options.numbering
+    column: usize,
+    section: Option<&'a [u8]>,
+    hash: Option<u64>,
+    encoding: Option<&'static str>,
+    // the line with its terminator, only with include_raw_line
+    raw_line: Option<&'a [u8]>,
+    blame: Option<&'a BlameInfo>,
+    // the lines around it, empty without context
+    context_before: Vec<&'a [u8]>,
+    context_after: Vec<&'a [u8]>,
+}
+
+// Where the walker hands off what it finds, so run_search can send results
+// while count_matches only tallies them
+trait Sink: Sync {
+    fn emit(&self, result: SearchResult);
+
+    // content hits come through here so sinks that only count can skip building the line text
+    fn content_match(&self, path: &Path, hit: LineHit<'_>) {
+        self.emit(content_result(path, hit));
+    }
+}
+
+// The ContentMatch a LineHit stands for
+fn content_result(path: &Path, hit: LineHit<'_>) -> SearchResult {
+    SearchResult::ContentMatch {
+        path: path.to_path_buf(),
+        line_number: hit.line_number,
+        line_text: String::from_utf8_lossy(hit.line).into_owned(),
+        truncated: hit.truncated,
+        pattern: hit.pattern,
+        match_start_col: hit.span.0,
+        match_end_col: hit.span.1,
+        column: hit.column,
+        section: hit.section.map(|s| String::from_utf8_lossy(s).into_owned()),
+        hash: hit.hash,
+        encoding: hit.encoding,
+        raw_line: hit.raw_line.map(<[u8]>::to_vec).unwrap_or_default(),
+        blame: hit.blame.cloned().map(Box::new),
+        context_before: hit.context_before.iter().map(|l| String::from_utf8_lossy(l).into_owned()).collect(),
+        context_after: hit.context_after.iter().map(|l| String::from_utf8_lossy(l).into_owned()).collect(),
+    }
+}
+
+impl Sink for std::sync::mpsc::Sender<SearchResult> {
+    fn emit(&self, result: SearchResult) {
+        let _ = self.send(result);
+    }
+}
+
+// A bounded channel for run_search_bounded. Workers block while it's full, dropping the receiver
+// wakes them up and cancels the rest of the search
+struct Bounded<'a> {
+    tx: std::sync::mpsc::SyncSender<SearchResult>,
+    cancel: &'a AtomicBool,
+}
+
+impl Sink for Bounded<'_> {
+    fn emit(&self, result: SearchResult) {
+        if self.tx.send(result).is_err() {
+            self.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+// Writes every result to a writer in whatever format encode produces, for run_search_json and
+// run_search_msgpack. The first failed write cancels the search and is kept to hand back at the end
+#[cfg(any(feature = "json", feature = "msgpack"))]
+struct Encoded<'a, W> {
+    writer: Mutex<W>,
+    encode: fn(&SearchResult) -> Vec<u8>,
+    cancel: &'a AtomicBool,
+    failed: Mutex<Option<std::io::Error>>,
+}
 
-            // Sets the path reference and file name we will use later
-            let path = entry.path().to_path_buf();
-            let file_name_str = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+#[cfg(any(feature = "json", feature = "msgpack"))]
+impl<'a, W: std::io::Write + Send> Encoded<'a, W> {
+    fn new(writer: W, encode: fn(&SearchResult) -> Vec<u8>, cancel: &'a AtomicBool) -> Self {
+        Encoded { writer: Mutex::new(writer), encode, cancel, failed: Mutex::new(None) }
+    }
+
+    fn run(self, options: SearchOptions) -> Result<(), SearchError> {
+        walk(options, self.cancel, &self)?;
+        match self.failed.into_inner().unwrap() {
+            Some(e) => Err(SearchError::Io(e)),
+            None => Ok(()),
+        }
+    }
+}
 
-            let mut file_name_match = false;
+#[cfg(any(feature = "json", feature = "msgpack"))]
+impl<W: std::io::Write + Send> Sink for Encoded<'_, W> {
+    fn emit(&self, result: SearchResult) {
+        let line = (self.encode)(&result);
+        let mut writer = self.writer.lock().unwrap();
+        let mut failed = self.failed.lock().unwrap();
+        if failed.is_some() {
+            return;
+        }
+        // flushed every result so whatever's reading the other end sees them as they're found
+        if let Err(e) = writer.write_all(&line).and_then(|_| writer.flush()) {
+            *failed = Some(e);
+            self.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+// Hands each result to the user's postprocessor on its way to the real sink. Content hits get
+// built in full first since the postprocessor works on a SearchResult, without one everything
+// goes straight through
+struct Postprocessed<'a, S> {
+    inner: &'a S,
+    post: Option<&'a Postprocessor>,
+}
 
-            // if the File name field has a value it'll come back as true so this knows to search for the inputted file name
-            if let Some(ref fm) = conf.file_matcher {
-                // uses the AhoCorasick match function to confirm matches
-                if fm.is_match(file_name_str.as_ref()) {
-                    file_name_match = true;
-                    // Sends that data to the egui
-                    let _ = tx.send(SearchResult::FileNameMatch { path: path.clone() });
+impl<S: Sink> Sink for Postprocessed<'_, S> {
+    fn emit(&self, result: SearchResult) {
+        match self.post {
+            Some(post) => {
+                if let Some(result) = post(result) {
+                    self.inner.emit(result);
                 }
-            } else {
-                file_name_match = true;
             }
+            None => self.inner.emit(result),
+        }
+    }
 
-            // If the Text field has a value it'll come back as true and will begin the search
-            if let Some(ref tm) = conf.text_matcher {
-                if file_name_match && entry.file_type().map_or(false, |ft| ft.is_file()) {
-                    let matches_ext = conf.allowed_exts.as_ref().map_or(true, |exts| {
-                        path.extension()
-                            .and_then(|e| e.to_str())
-                            .map(|e| exts.contains(&e.to_lowercase()))
-                            .unwrap_or(false)
-                    });
+    fn content_match(&self, path: &Path, hit: LineHit<'_>) {
+        match self.post {
+            Some(_) => self.emit(content_result(path, hit)),
+            None => self.inner.content_match(path, hit),
+        }
+    }
+}
+
+// Lets max_results matches through and then cancels the search, the ones other threads are still
+// sending after that get dropped. Everything else goes straight through
+struct Capped<'a, S> {
+    inner: &'a S,
+    max: Option<usize>,
+    sent: AtomicUsize,
+    cancel: &'a AtomicBool,
+}
+
+impl<'a, S: Sink> Capped<'a, S> {
+    fn new(inner: &'a S, max: Option<usize>, cancel: &'a AtomicBool) -> Self {
+        Capped { inner, max, sent: AtomicUsize::new(0), cancel }
+    }
+
+    // true when the match gets to go out, the one that reaches the limit stops the walk
+    fn take(&self) -> bool {
+        let Some(max) = self.max else {
+            return true;
+        };
+        let n = self.sent.fetch_add(1, Ordering::Relaxed);
+        if n + 1 >= max {
+            self.cancel.store(true, Ordering::Relaxed);
+        }
+        n < max
+    }
+
+    fn finish(&self) {
+        if let Some(max) = self.max
+            && self.sent.load(Ordering::Relaxed) >= max
+        {
+            self.inner.emit(SearchResult::ResultLimitReached { results: max });
+        }
+    }
+}
+
+impl<S: Sink> Sink for Capped<'_, S> {
+    fn emit(&self, result: SearchResult) {
+        if matches!(result, SearchResult::FileNameMatch { .. } | SearchResult::ContentMatch { .. }) && !self.take() {
+            return;
+        }
+        self.inner.emit(result);
+    }
+
+    fn content_match(&self, path: &Path, hit: LineHit<'_>) {
+        if self.take() {
+            self.inner.content_match(path, hit);
+        }
+    }
+}
+
+// Just adds up the hits for count_matches
+#[derive(Default)]
+struct Tally {
+    matches: AtomicUsize,
+}
+
+impl Sink for Tally {
+    // content matches only come through here when a postprocessor built them
+    fn emit(&self, result: SearchResult) {
+        if let SearchResult::FileNameMatch { .. } | SearchResult::ContentMatch { .. } = result {
+            self.matches.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn content_match(&self, _path: &Path, _hit: LineHit<'_>) {
+        self.matches.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// What the walk itself kept track of
+struct WalkTotals {
+    files_scanned: usize,
+    files_matched: usize,
+    remote_skipped: usize,
+}
+
+// Fails up front with PatternError when a query doesn't compile, before anything is walked
+pub fn run_search(options: SearchOptions, tx: std::sync::mpsc::Sender<SearchResult>, thread_token: Arc<AtomicBool>) -> Result<(), SearchError> {
+    walk(options, &thread_token, &tx).map(|_| ())
+}
+
+// Same as run_search but over a sync_channel, so when the consumer falls behind the walker
+// waits instead of piling results up in memory. A waiting walker doesn't see thread_token, to
+// cancel then drop the receiver (thread_token still works while the consumer is keeping up)
+pub fn run_search_bounded(options: SearchOptions, tx: std::sync::mpsc::SyncSender<SearchResult>, thread_token: Arc<AtomicBool>) -> Result<(), SearchError> {
+    let sink = Bounded { tx, cancel: &thread_token };
+    walk(options, &thread_token, &sink).map(|_| ())
+}
+
+// Same as run_search but writes each result to writer (a file, pipe, socket...) as a line of JSON,
+// e.g. {"type":"content_match","path":"src/main.rs","line_number":3,...}. If a write fails the
+// search stops and that error comes back as SearchError::Io
+#[cfg(feature = "json")]
+pub fn run_search_json(options: SearchOptions, writer: impl std::io::Write + Send, thread_token: Arc<AtomicBool>) -> Result<(), SearchError> {
+    Encoded::new(writer, |result| wire::to_json_line(result).into_bytes(), &thread_token).run(options)
+}
+
+// Reads back what run_search_json or results_file wrote, one result per line, for looking at a search
+// again without running it. Lines that aren't a result (cut off, edited, from something else) are
+// skipped and counted in the second number, blank ones are just skipped. A read error stops it
+#[cfg(feature = "json")]
+pub fn load_results_json(reader: impl std::io::BufRead) -> Result<(Vec<SearchResult>, usize), SearchError> {
+    let mut results = Vec::new();
+    let mut skipped = 0;
+    for line in reader.split(b'\n') {
+        let line = line?;
+        let line = String::from_utf8_lossy(&line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(result) => results.push(result),
+            Err(_) => skipped += 1,
+        }
+    }
+    Ok((results, skipped))
+}
+
+// What export_results writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    // one array of {"type", "path", "line_number", "line_text"} objects, needs the json feature
+    #[cfg(feature = "json")]
+    Json,
+    // a type,path,line_number,line_text header and a row per result
+    Csv,
+}
+
+// Writes the matches in results for other tools to pick up, everything that isn't a ContentMatch or
+// a FileNameMatch is left out. A file name match has no line, so its line_number and line_text are
+// null (empty in the CSV)
+pub fn export_results(results: &[SearchResult], format: ExportFormat, writer: impl std::io::Write) -> Result<(), SearchError> {
+    use std::io::Write;
+    let mut writer = std::io::BufWriter::new(writer);
+    let rows = results.iter().filter_map(|result| match result {
+        SearchResult::ContentMatch { path, line_number, line_text, .. } => Some(("content_match", path, Some((*line_number, line_text.as_str())))),
+        SearchResult::FileNameMatch { path, .. } => Some(("file_name_match", path, None)),
+        _ => None,
+    });
+
+    match format {
+        #[cfg(feature = "json")]
+        ExportFormat::Json => {
+            let mut empty = true;
+            for (kind, path, line) in rows {
+                writer.write_all(if empty { b"[\n" } else { b",\n" })?;
+                let row = wire::ExportRow {
+                    kind,
+                    path: path.to_string_lossy(),
+                    line_number: line.map(|(n, _)| n),
+                    line_text: line.map(|(_, text)| text),
+                };
+                serde_json::to_writer(&mut writer, &row).map_err(std::io::Error::from)?;
+                empty = false;
+            }
+            writer.write_all(if empty { b"[]\n" } else { b"\n]\n" })?;
+        }
+        ExportFormat::Csv => {
+            writer.write_all(b"type,path,line_number,line_text\r\n")?;
+            for (kind, path, line) in rows {
+                let (number, text) = line.map(|(n, text)| (n.to_string(), csv_field(text))).unwrap_or_default();
+                write!(writer, "{},{},{},{}\r\n", kind, csv_field(&path.to_string_lossy()), number, text)?;
+            }
+        }
+    }
+    Ok(writer.flush()?)
+}
+
+// RFC 4180 quoting: a field with a comma, quote or line break in it goes in quotes with its quotes doubled
+fn csv_field(text: &str) -> Cow<'_, str> {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\"")).into()
+    } else {
+        text.into()
+    }
+}
+
+// Same as run_search_json but in MessagePack, much smaller and quicker to parse for feeding
+// another process. Each result is one MessagePack value written straight after the last, read
+// them back with rmp_serde::from_read::<_, SearchResult> until it hits the end of the stream
+#[cfg(feature = "msgpack")]
+pub fn run_search_msgpack(options: SearchOptions, writer: impl std::io::Write + Send, thread_token: Arc<AtomicBool>) -> Result<(), SearchError> {
+    Encoded::new(writer, msgpack::encode, &thread_token).run(options)
+}
+
+// Runs the same walk as run_search but only counts, no results get built or sent
+pub fn count_matches(options: SearchOptions) -> Result<MatchCounts, SearchError> {
+    let tally = Tally::default();
+    let totals = walk(options, &AtomicBool::new(false), &tally)?;
+    Ok(MatchCounts {
+        files_scanned: totals.files_scanned,
+        files_matched: totals.files_matched,
+        total_matches: tally.matches.into_inner(),
+        remote_skipped: totals.remote_skipped,
+    })
+}
+
+// Runs the normal name/content checks against a single file instead of walking a folder,
+// options.root is ignored. Goes through the same postprocessor, max_results and results_file as a
+// walk, and cancelling thread_token stops it partway through the file
+pub fn search_file(path: &Path, options: SearchOptions, tx: std::sync::mpsc::Sender<SearchResult>, thread_token: Arc<AtomicBool>) -> Result<(), SearchError> {
+    with_sinks(options, &thread_token, &tx, |options, sink| {
+        let errors = ErrorCounts::new(options.error_reporting);
+        let conf = SearchConfig::new(options)?;
+        conf.search_entry(path, path.is_file(), sink, &errors, &thread_token);
+        errors.finish(sink);
+        conf.finish(sink);
+        Ok(())
+    })
+}
+
+fn walk<S: Sink>(options: SearchOptions, thread_token: &AtomicBool, tx: &S) -> Result<WalkTotals, SearchError> {
+    with_sinks(options, thread_token, tx, |options, sink| walk_all(options, thread_token, sink))
+}
+
+// every entry point goes through here, so this is where the postprocessor, max_results and the
+// results file get put in front of the sink before search runs
+fn with_sinks<S: Sink, T>(
+    mut options: SearchOptions,
+    thread_token: &AtomicBool,
+    tx: &S,
+    search: impl FnOnce(SearchOptions, &Postprocessed<'_, Capped<'_, results_file::Recorded<'_, S>>>) -> Result<T, SearchError>,
+) -> Result<T, SearchError> {
+    let post = options.postprocessor.take();
+    let recorded = results_file::Recorded::open(&options, tx)?;
+    // the limit counts what the postprocessor lets through, not what it drops
+    let capped = Capped::new(&recorded, options.max_results, thread_token);
+    let found = search(options, &Postprocessed { inner: &capped, post: post.as_ref() });
+    capped.finish();
+    // whatever was found before a failure still gets written out
+    let written = recorded.finish();
+    let found = found?;
+    written?;
+    Ok(found)
+}
+
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "info", skip_all, fields(root = %options.root)))]
+fn walk_all<S: Sink>(options: SearchOptions, thread_token: &AtomicBool, tx: &S) -> Result<WalkTotals, SearchError> {
+    #[cfg(feature = "tracing")]
+    let started = Instant::now();
+    let errors = ErrorCounts::new(options.error_reporting);
+    let config = SearchConfig::new(options)?;
+    let counters = Counters::default();
 
-                    if matches_ext {
-                        if let Ok(file) = File::open(&path) {
-                            if let Ok(mmap) = unsafe { Mmap::map(&file) } {
-                                if memchr(0, &mmap[..1024.min(mmap.len())]).is_none() {
-                                    if let Err(e) = process_file_content(&path, &mmap, tm, &tx) {
-                                        eprintln!("Error processing {}: {}", path.display(), e);
-                                    }
-                                }
+    if let Some(depth) = config.depth_capped {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(depth, "root is on a network or removable drive, walking it only this deep");
+        tx.emit(SearchResult::DepthCapped { root: PathBuf::from(&config.options.root), depth });
+    }
+
+    // the git modes swap the directory walk for a list of files from the repo,
+    // outside of a repo they just walk like normal
+    let root = Path::new(&config.options.root);
+    let candidates = if config.options.git_changed_only {
+        git::changed_files(root)
+    } else if config.options.git_tracked_only {
+        git::tracked_files(root)
+    } else {
+        None
+    };
+
+    match candidates {
+        Some(paths) => {
+            if config.options.count_total {
+                tx.emit(SearchResult::ProgressTotal(paths.len()));
+            }
+            search_candidates(&config, &paths, thread_token, tx, &counters, &errors)
+        }
+        None => match config.options.checkpoint {
+            Some(ref file) if root.is_dir() => walk_resumable(&config, file, thread_token, tx, &counters, &errors)?,
+            _ => {
+                if config.options.count_total {
+                    // the count stops with the walk, a total that comes in after the last entry is no use
+                    let walked = AtomicBool::new(false);
+                    std::thread::scope(|s| {
+                        s.spawn(|| {
+                            if let Some(total) = count_entries(&config, &[thread_token, &walked]) {
+                                tx.emit(SearchResult::ProgressTotal(total));
                             }
-                        }
-                    }
+                        });
+                        walk_tree(&config, None, thread_token, tx, &counters, &errors);
+                        walked.store(true, Ordering::Relaxed);
+                    });
+                } else {
+                    walk_tree(&config, None, thread_token, tx, &counters, &errors)
                 }
             }
+        },
+    }
 
-            WalkState::Continue
-        })
-    });
+    errors.finish(tx);
+    config.finish(tx);
+
+    let totals = WalkTotals {
+        files_scanned: counters.files_scanned.into_inner(),
+        files_matched: counters.files_matched.into_inner(),
+        remote_skipped: counters.remote_skipped.into_inner(),
+    };
+    #[cfg(feature = "tracing")]
+    tracing::info!(
+        files_scanned = totals.files_scanned,
+        files_matched = totals.files_matched,
+        timed_out = config.timed_out.load(Ordering::Relaxed),
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "walk finished"
+    );
+    Ok(totals)
 }
 
-fn is_important(entry: &ignore::DirEntry) -> bool {
-    let name = entry.file_name().to_string_lossy();
-    !matches!(
-        name.as_ref(),
-        "Windows" | "Program Files" | "Program Files (x86)" | "AppData" | "Temp" | ".git" | "node_modules"
-    )
+// Running totals shared by every walker thread
+#[derive(Default)]
+struct Counters {
+    scanned_count: AtomicUsize,
+    files_scanned: AtomicUsize,
+    files_matched: AtomicUsize,
+    remote_skipped: AtomicUsize,
 }
 
-fn process_file_content(path: &Path, mmap: &[u8], ac: &AhoCorasick, tx: &std::sync::mpsc::Sender<SearchResult>) -> Result<(), SearchError> {
-    let mut last_counted_pos = 0;
-    let mut current_line_number = 1;
+impl Counters {
+    // counts a visited entry and sends progress every 50 of them
+    fn tick<S: Sink>(&self, tx: &S) {
+        let current_val = self.scanned_count.fetch_add(1, Ordering::Relaxed);
+        if (current_val + 1).is_multiple_of(50) {
+            tx.emit(SearchResult::ProgressUpdate(current_val + 1));
+        }
+    }
 
-    for mat in ac.find_iter(mmap) {
-        let match_start = mat.start();
-        current_line_number += bytecount::count(&mmap[last_counted_pos..match_start], b'\n');
-        last_counted_pos = match_start;
+    // true when local_only leaves the path out, which gets counted and reported
+    fn skip_remote<S: Sink>(&self, conf: &SearchConfig, path: &Path, tx: &S) -> bool {
+        if !conf.on_slow_volume(path) {
+            return false;
+        }
+        self.remote_skipped.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(path = %path.display(), "skipped, not on a local drive");
+        tx.emit(SearchResult::RemoteSkipped { path: path.to_path_buf() });
+        true
+    }
 
-        let line_start = memrchr(b'\n', &mmap[..match_start]).map(|p| p + 1).unwrap_or(0);
-        let line_end = memchr(b'\n', &mmap[match_start..]).map(|p| match_start + p).unwrap_or(mmap.len());
+    // runs the checks on one file (or folder) and keeps the totals up to date
+    fn search<S: Sink>(&self, conf: &SearchConfig, path: &Path, is_file: bool, tx: &S, errors: &ErrorCounts, cancel: &AtomicBool) {
+        if is_file {
+            self.files_scanned.fetch_add(1, Ordering::Relaxed);
+        }
+        if conf.search_entry(path, is_file, tx, errors, cancel) {
+            self.files_matched.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
 
-        let line_bytes = &mmap[line_start..line_end];
-        let line_text = String::from_utf8_lossy(if line_bytes.ends_with(b"\r") {
-            &line_bytes[..line_bytes.len() - 1]
-        } else {
-            line_bytes
-        }).into_owned();
+// Walks the root one top level folder at a time (the root's own files first), writing each one
+// down in the checkpoint file as it finishes and skipping the ones an earlier run already did
+fn walk_resumable<S: Sink>(config: &SearchConfig, file: &Path, thread_token: &AtomicBool, tx: &S, counters: &Counters, errors: &ErrorCounts) -> Result<(), SearchError> {
+    let mut checkpoint = checkpoint::Checkpoint::open(file, &config.options)?;
+    let mut dirs: Vec<_> = std::fs::read_dir(&config.options.root)?
+        .filter_map(|entry| entry.ok())
+        // a symlinked folder is one more pass when links are followed
+        .filter(|entry| if config.options.follow_symlinks { entry.path().is_dir() } else { entry.file_type().is_ok_and(|ft| ft.is_dir()) })
+        .map(|entry| entry.file_name())
+        .collect();
+    dirs.sort();
 
-        let _ = tx.send(SearchResult::ContentMatch {
-            path: path.to_path_buf(),
-            line_number: current_line_number,
-            line_text,
-        });
+    for pass in std::iter::once(checkpoint::Pass::Files).chain(dirs.into_iter().map(checkpoint::Pass::Dir)) {
+        if checkpoint.is_done(&pass) {
+            continue;
+        }
+        walk_tree(config, Some(&pass), thread_token, tx, counters, errors);
+        // cancelled or out of time, whatever this pass got through gets done again next time
+        if thread_token.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        checkpoint.mark_done(&pass)?;
     }
+    checkpoint.finish()?;
     Ok(())
-}
\ No newline at end of file
+}
+
+// pass limits the walk to one slice of the root for walk_resumable, None walks all of it
+fn walk_tree<S: Sink>(config: &SearchConfig, pass: Option<&checkpoint::Pass>, thread_token: &AtomicBool, tx: &S, counters: &Counters, errors: &ErrorCounts) {
+    let options = &config.options;
+
+    // only_gitignored does the gitignore matching itself, so the walker has to keep what it would drop
+    let gitignored = if options.only_gitignored {
+        match gitignored::IgnoredFilter::new(Path::new(&options.root), options.respect_global_gitignore) {
+            Some(filter) => Some(filter),
+            None => return,
+        }
+    } else {
+        None
+    };
+    let git_rules = gitignored.is_none();
+
+    // allows multiple to run by splitting them across threads
+    let walker = walk_builder(options, git_rules).build_parallel();
+
+    // Begins walking through directories
+    // snapshot mode lists everything first and searches the list once the walk is done
+    let snapshot = options.snapshot.then(|| Mutex::new(Vec::new()));
+
+    walker.run(|| {
+        let cancel_status = thread_token;
+        let gitignored = gitignored.as_ref();
+        let snapshot = snapshot.as_ref();
+
+        // files/directories data being accessed
+        Box::new(move |result| {
+            counters.tick(tx);
+            if cancel_status.load(Ordering::Relaxed) {
+                return WalkState::Quit;
+            }
+            // running out of time cancels the search the same way the cancel button does
+            if config.out_of_time() {
+                cancel_status.store(true, Ordering::Relaxed);
+                return WalkState::Quit;
+            }
+
+            // handles issues with permissions blocking entry
+            let entry = match result {
+                Ok(e) => e,
+                Err(err) => {
+                    let path = error_path(&err).map(Path::to_path_buf);
+                    let source = match path {
+                        Some(ref p) if p.is_dir() => ErrorSource::Directory,
+                        _ => ErrorSource::File,
+                    };
+                    errors.report(tx, path, source, err.to_string());
+                    return WalkState::Continue;
+                }
+            };
+
+            // the root still has to be walked into when it's not part of the pass itself
+            if let Some(pass) = pass
+                && !pass.includes(&entry)
+            {
+                return if entry.depth() == 0 { WalkState::Continue } else { WalkState::Skip };
+            }
+
+            if config.is_excluded(entry.path()) {
+                return WalkState::Skip;
+            }
+
+            // mounts only start at folders, so there's no need to check every file
+            if entry.file_type().is_some_and(|ft| ft.is_dir()) && counters.skip_remote(config, entry.path(), tx) {
+                return WalkState::Skip;
+            }
+
+            if entry.depth() > 0 && config.over_dir_cap(entry.path()) {
+                return WalkState::Skip;
+            }
+
+            // Skips over most files with permission issues/massive sizes
+            if entry.depth() > 0 && !options.search_all && !config.is_important(&entry) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(path = %entry.path().display(), "skipped by the skip list");
+                return WalkState::Skip;
+            }
+
+            let is_file = entry.file_type().is_some_and(|ft| ft.is_file());
+
+            // something git doesn't ignore still gets walked into, there can be ignored files further down
+            if let Some(filter) = gitignored
+                && !filter.is_ignored(entry.path(), entry.file_type().is_some_and(|ft| ft.is_dir()))
+            {
+                return WalkState::Continue;
+            }
+
+            // same for hidden_only, a visible folder can still have dotfiles in it
+            if options.hidden_only && !config.in_hidden(entry.path()) {
+                return WalkState::Continue;
+            }
+
+            match snapshot {
+                Some(snapshot) => snapshot.lock().unwrap().push(Snapshotted::of(&entry, is_file)),
+                None => counters.search(config, entry.path(), is_file, tx, errors, cancel_status),
+            }
+
+            WalkState::Continue
+        })
+    });
+
+    if let Some(snapshot) = snapshot {
+        search_snapshot(config, snapshot.into_inner().unwrap(), thread_token, tx, counters, errors);
+    }
+}
+
+// Sets up walking through directories starting from the farthest entered, git_rules off leaves the
+// gitignore matching to only_gitignored
+fn walk_builder(options: &SearchOptions, git_rules: bool) -> ignore::WalkBuilder {
+    let mut builder = ignore::WalkBuilder::new(&options.root);
+    builder
+        .max_depth(options.max_depth)
+        .follow_links(options.follow_symlinks)
+        .threads(options.threads.unwrap_or(0))
+        // hidden files are only skipped on windows (and hidden_only checks them itself)
+        .hidden(cfg!(windows) && !options.hidden_only)
+        .git_ignore(options.respect_gitignore && git_rules)
+        .git_exclude(options.respect_gitignore && git_rules)
+        .parents(options.respect_parent_ignores)
+        .git_global(options.respect_global_gitignore && git_rules);
+    builder
+}
+
+// Walks next to the search counting what it will visit: every entry tick counts, minus the
+// folders the walk skips. It doesn't look at anything, so it gets ahead of the search quickly. None
+// when one of the stop flags was set before it got to the end
+fn count_entries(config: &SearchConfig, stop: &[&AtomicBool]) -> Option<usize> {
+    let options = &config.options;
+    let total = AtomicUsize::new(0);
+    let gave_up = AtomicBool::new(false);
+
+    walk_builder(options, !options.only_gitignored).build_parallel().run(|| {
+        let (total, gave_up) = (&total, &gave_up);
+        Box::new(move |result| {
+            if stop.iter().any(|flag| flag.load(Ordering::Relaxed)) {
+                gave_up.store(true, Ordering::Relaxed);
+                return WalkState::Quit;
+            }
+            total.fetch_add(1, Ordering::Relaxed);
+            let Ok(entry) = result else {
+                return WalkState::Continue;
+            };
+            if config.is_excluded(entry.path()) || (entry.depth() > 0 && !options.search_all && !config.is_important(&entry)) {
+                return WalkState::Skip;
+            }
+            WalkState::Continue
+        })
+    });
+    (!gave_up.into_inner()).then(|| total.into_inner())
+}
+
+// An entry as the walk found it in snapshot mode, size and modified time are only kept for files
+struct Snapshotted {
+    path: PathBuf,
+    is_file: bool,
+    stamp: Option<(u64, SystemTime)>,
+}
+
+impl Snapshotted {
+    fn of(entry: &ignore::DirEntry, is_file: bool) -> Self {
+        let stamp = if is_file { entry.metadata().ok().and_then(|m| stamp(&m)) } else { None };
+        Snapshotted { path: entry.path().to_path_buf(), is_file, stamp }
+    }
+}
+
+fn stamp(meta: &std::fs::Metadata) -> Option<(u64, SystemTime)> {
+    Some((meta.len(), meta.modified().ok()?))
+}
+
+// The second half of snapshot mode, searches exactly what the walk listed. A file that changed
+// since then is reported and left out instead of being searched half written
+fn search_snapshot<S: Sink>(config: &SearchConfig, entries: Vec<Snapshotted>, thread_token: &AtomicBool, tx: &S, counters: &Counters, errors: &ErrorCounts) {
+    entries.par_iter().for_each(|entry| {
+        if thread_token.load(Ordering::Relaxed) {
+            return;
+        }
+        if config.out_of_time() {
+            thread_token.store(true, Ordering::Relaxed);
+            return;
+        }
+        if entry.is_file {
+            let now = std::fs::metadata(&entry.path).ok().and_then(|m| stamp(&m));
+            if now.is_none() || now != entry.stamp {
+                let message = "changed after the file list was taken, skipped".to_string();
+                errors.report(tx, Some(entry.path.clone()), ErrorSource::File, message);
+                return;
+            }
+        }
+        counters.search(config, &entry.path, entry.is_file, tx, errors, thread_token);
+    });
+}
+
+// Searches a fixed list of files spread over the rayon pool instead of walking for them
+fn search_candidates<S: Sink>(config: &SearchConfig, paths: &[PathBuf], thread_token: &AtomicBool, tx: &S, counters: &Counters, errors: &ErrorCounts) {
+    let root = Path::new(&config.options.root);
+
+    paths.par_iter().for_each(|path| {
+        if thread_token.load(Ordering::Relaxed) {
+            return;
+        }
+        if config.out_of_time() {
+            thread_token.store(true, Ordering::Relaxed);
+            return;
+        }
+        counters.tick(tx);
+
+        // keeps max_depth meaning the same thing it does for the walk
+        let depth = path.strip_prefix(root).map(|p| p.components().count()).unwrap_or(0);
+        if config.options.max_depth.is_some_and(|max| depth > max) || config.is_excluded(path) {
+            return;
+        }
+        if counters.skip_remote(config, path, tx) {
+            return;
+        }
+        if config.options.hidden_only && !config.in_hidden(path) {
+            return;
+        }
+
+        // tracked files can be deleted from the working tree, those just get skipped
+        counters.search(config, path, path.is_file(), tx, errors, thread_token);
+    });
+}
+
+impl SearchConfig {
+    fn new(mut options: SearchOptions) -> Result<Self, SearchError> {
+        let mut queries = options.text_query.iter().chain(&options.more_text_queries).chain(&options.file_query);
+        if options.ascii_only && queries.any(|q| !q.is_ascii()) {
+            return Err(SearchError::NonAsciiQuery);
+        }
+
+        let text_matcher = TextMatcher::new(&options)?;
+
+        // collects the file name and attaches its engine for matching
+        let file_ignore_case = options.ignore_case || options.file_ignore_case;
+        let file_matcher = options.file_query.as_ref().map(|f| {
+            if options.phonetic {
+                let query = phonetic::word_codes(f).into_iter().map(|(word, code)| (word.to_string(), code)).collect::<Vec<_>>();
+                // a query without a single letter in it can't sound like anything
+                if query.is_empty() { Err(SearchError::PatternError) } else { Ok(NameMatcher::Phonetic(query)) }
+            } else if options.file_regex || (file_ignore_case && !f.is_ascii() && !options.file_glob) {
+                let pattern = if options.file_regex { Cow::Borrowed(f.as_str()) } else { Cow::Owned(escape_folding(f)) };
+                RegexBuilder::new(&pattern)
+                    .case_insensitive(file_ignore_case)
+                    .build()
+                    .map(NameMatcher::Regex)
+                    .map_err(|_| SearchError::PatternError)
+            } else if options.file_glob {
+                let fold = file_ignore_case && !f.is_ascii();
+                globset::GlobBuilder::new(&if fold { f.to_lowercase() } else { f.clone() })
+                    .case_insensitive(file_ignore_case)
+                    .literal_separator(true)
+                    .build()
+                    .map(|glob| NameMatcher::Glob { glob: glob.compile_matcher(), fold })
+                    .map_err(|_| SearchError::PatternError)
+            } else {
+                AhoCorasickBuilder::new()
+                    .ascii_case_insensitive(file_ignore_case)
+                    .build([f])
+                    .map(NameMatcher::Literal)
+                    .map_err(|_| SearchError::PatternError)
+            }
+        }).transpose()?;
+
+        let reference = options.differs_from.as_ref().map(|p| Reference::load(p, file_ignore_case)).transpose()?;
+
+        // collects all file_types and separates them for filtering during actual searching
+        let allowed_exts = options.file_types.as_ref().map(|s| {
+            s.split(',').map(|ext| ext.trim().to_lowercase()).collect::<HashSet<_>>()
+        });
+
+        // a marker that doesn't compile just leaves sections off
+        let section_marker = options.section_marker.as_ref().and_then(|m| Regex::new(m).ok());
+
+        let volumes = (options.local_only || options.adaptive_depth).then(volume::Volumes::load);
+        let absolute_root = absolute_path(Path::new(&options.root));
+        let slow_root = volumes.as_ref().zip(absolute_root.as_ref()).is_some_and(|(volumes, root)| volumes.is_slow(root));
+        let depth_capped = adaptive_max_depth(&options, slow_root);
+        options.max_depth = options.max_depth.or(depth_capped);
+
+        Ok(SearchConfig {
+            text_matcher,
+            file_matcher,
+            reference,
+            allowed_exts,
+            section_marker,
+            deadline: options.max_duration.map(|d| Instant::now() + d),
+            timed_out: AtomicBool::new(false),
+            volumes,
+            depth_capped,
+            seen_links: Mutex::new(HashSet::new()),
+            dir_entries: Mutex::new(HashMap::new()),
+            loaded: Mutex::new(0),
+            unloaded: Condvar::new(),
+            excluded: options.exclude_paths.iter().filter_map(|p| absolute_path(p)).collect(),
+            absolute_root,
+            options,
+        })
+    }
+
+    // false for what the skip list leaves out, a name in exclude_dirs or one of the kernel's folders
+    fn is_important(&self, entry: &ignore::DirEntry) -> bool {
+        let name = entry.file_name().to_string_lossy();
+        let skipped = self.options.exclude_dirs.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern.as_str(),
+        });
+        !skipped && !is_system_path(entry.path())
+    }
+
+    // true for anything in exclude_paths, or under one of them
+    fn is_excluded(&self, path: &Path) -> bool {
+        if self.excluded.is_empty() {
+            return false;
+        }
+        let path = self.absolute(path);
+        self.excluded.iter().any(|excluded| path.starts_with(excluded))
+    }
+
+    // true with local_only for anything on a network share or removable drive
+    fn on_slow_volume(&self, path: &Path) -> bool {
+        self.options.local_only && self.volumes.as_ref().is_some_and(|volumes| volumes.is_slow(&self.absolute(path)))
+    }
+
+    // a path the walker handed out, with the root swapped for its absolute form
+    fn absolute(&self, path: &Path) -> PathBuf {
+        match (&self.absolute_root, path.strip_prefix(&self.options.root)) {
+            (Some(root), Ok(rel)) => root.join(rel),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    // true when the path itself or a folder it's in (below the root) is hidden
+    fn in_hidden(&self, path: &Path) -> bool {
+        let root = Path::new(&self.options.root);
+        let Ok(rel) = path.strip_prefix(root) else {
+            return is_hidden(path);
+        };
+        let mut current = root.to_path_buf();
+        rel.components().any(|component| {
+            current.push(component);
+            is_hidden(&current)
+        })
+    }
+
+    // true once max_duration has passed, remembers it so TimedOut gets sent at the end
+    fn out_of_time(&self) -> bool {
+        if self.timed_out.load(Ordering::Relaxed) {
+            return true;
+        }
+        if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            self.timed_out.store(true, Ordering::Relaxed);
+            return true;
+        }
+        false
+    }
+
+    // true for a file over max_file_size, checked before anything gets opened or mapped
+    fn too_big(&self, path: &Path) -> bool {
+        self.options.max_file_size.is_some_and(|max| std::fs::metadata(path).is_ok_and(|m| m.len() > max))
+    }
+
+    // waits until the file fits in max_loaded_bytes (or nothing else is loaded), None when there's no budget
+    fn borrow_bytes(&self, path: &Path) -> Option<Loan<'_>> {
+        let budget = self.options.max_loaded_bytes?;
+        let bytes = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        let mut loaded = self.loaded.lock().unwrap();
+        while *loaded > 0 && *loaded + bytes > budget {
+            loaded = self.unloaded.wait(loaded).unwrap();
+        }
+        *loaded += bytes;
+        Some(Loan { config: self, bytes })
+    }
+
+    // counts the entry against its folder, true once the folder is past max_entries_per_dir
+    fn over_dir_cap(&self, path: &Path) -> bool {
+        let (Some(max), Some(dir)) = (self.options.max_entries_per_dir, path.parent()) else {
+            return false;
+        };
+        let mut entries = self.dir_entries.lock().unwrap();
+        let seen = entries.entry(dir.to_path_buf()).or_insert(0);
+        *seen += 1;
+        *seen > max
+    }
+
+    fn finish<S: Sink>(&self, tx: &S) {
+        if let Some(max) = self.options.max_entries_per_dir {
+            for (dir, &seen) in self.dir_entries.lock().unwrap().iter() {
+                if seen > max {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(path = %dir.display(), entries = seen, "folder has more entries than max_entries_per_dir");
+                    tx.emit(SearchResult::MoreEntries { path: dir.clone(), omitted: seen - max });
+                }
+            }
+        }
+        if self.timed_out.load(Ordering::Relaxed) {
+            tx.emit(SearchResult::TimedOut);
+        }
+    }
+
+    // Checks one entry's name and (for files) its content, returns true if anything matched. cancel
+    // stops the content scan partway
+    fn search_entry<S: Sink>(&self, path: &Path, is_file: bool, tx: &S, errors: &ErrorCounts, cancel: &AtomicBool) -> bool {
+        // in text only mode anything that sniffs as binary isn't a candidate at all
+        // (a .gz gets checked after it's decompressed instead, and files with an extractor are turned into text)
+        if self.options.text_only
+            && is_file
+            && !self.is_gzip(path)
+            && self.options.extractors.get(path).is_none()
+            && !sniff_is_text(path)
+        {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(path = %path.display(), "skipped as binary");
+            return false;
+        }
+
+        // Sets the file name we will use later
+        let file_name_str = if self.options.match_full_path {
+            let rel = path.strip_prefix(&self.options.root).unwrap_or(path);
+            let rel = rel.to_string_lossy();
+            if std::path::MAIN_SEPARATOR == '/' { rel } else { rel.replace(std::path::MAIN_SEPARATOR, "/").into() }
+        } else {
+            path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default()
+        };
+
+        let mut file_name_match = false;
+        let mut name_hit = false;
+
+        // if the File name field has a value it'll come back as true so this knows to search for the inputted file name
+        let name_found = match (&self.reference, &self.file_matcher) {
+            (Some(reference), _) if is_file => reference.differs(path),
+            (Some(_), _) => None,
+            // uses the AhoCorasick match function to confirm matches
+            (None, Some(fm)) => fm.find(file_name_str.as_ref()),
+            (None, None) => None,
+        };
+        if let Some(note) = name_found {
+            file_name_match = true;
+            name_hit = true;
+            // Sends that data to the egui
+            tx.emit(SearchResult::FileNameMatch { path: path.to_path_buf(), note });
+        }
+        if (self.file_matcher.is_none() && self.reference.is_none()) || self.options.combine == Combine::Or {
+            file_name_match = true;
+        }
+
+        let mut content_hits = 0;
+        let gzipped = self.is_gzip(path);
+
+        // If the Text field has a value it'll come back as true and will begin the search
+        if let Some(ref tm) = self.text_matcher
+            && file_name_match
+            && is_file
+        {
+            // for foo.log.gz the extension filter looks at the "log"
+            let ext_path = if gzipped { Path::new(path.file_stem().unwrap_or_default()) } else { path };
+            let matches_ext = self.allowed_exts.as_ref().is_none_or(|exts| {
+                ext_path.extension()
+                    .and_then(|e| e.to_str())
+                    .map(|e| exts.contains(&e.to_lowercase()))
+                    .unwrap_or(false)
+            });
+
+            // skips anything that doesn't look like text, then hands it to the line scanner
+            let mut scan = |path: &Path, bytes: &[u8]| {
+                let (encoding, bytes) = match self.options.detect_encoding.then(|| decode_bom(bytes)).flatten() {
+                    Some((encoding, decoded)) => (Some(encoding), decoded),
+                    None => (None, Cow::Borrowed(bytes)),
+                };
+                let bytes = bytes.as_ref();
+                if looks_like_text(bytes) {
+                    let bytes = match self.options.normalize_unicode {
+                        Some(form) => normalize(bytes, form),
+                        None => Cow::Borrowed(bytes),
+                    };
+                    match self.process_file_content(path, &bytes, encoding, tm, tx, cancel) {
+                        Ok(hits) => content_hits += hits,
+                        Err(e) => eprintln!("Error processing {}: {}", path.display(), e),
+                    }
+                } else {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(path = %path.display(), "skipped, looks binary");
+                }
+            };
+
+            if matches_ext && !self.too_big(path) && !self.seen_link(path) {
+                // a .gz is counted at its compressed size, there's no cheap way to know what it unpacks to
+                let _loan = self.borrow_bytes(path);
+                let started = Instant::now();
+                let contents = if gzipped {
+                    read_gzip(path).map(Contents::Owned)
+                } else {
+                    load_file(path, self.options.max_file_size)
+                };
+                match contents {
+                    Ok(contents) => match self.options.extractors.get(ext_path) {
+                        None => scan(path, contents.bytes()),
+                        Some(extractor) => match extractor.extract(path, contents.bytes()) {
+                            Ok(text) => scan(path, &text),
+                            Err(e) => errors.report(tx, Some(path.to_path_buf()), ErrorSource::File, e.to_string()),
+                        },
+                    },
+                    Err(e) => errors.report(tx, Some(path.to_path_buf()), ErrorSource::File, e.to_string()),
+                }
+                if let Some(threshold) = self.options.report_timing
+                    && started.elapsed() >= threshold
+                {
+                    tx.emit(SearchResult::FileTiming { path: path.to_path_buf(), micros: started.elapsed().as_micros() as u64 });
+                }
+
+                // alternate data streams get searched like files of their own, reported as file.txt:stream
+                #[cfg(windows)]
+                if self.options.alternate_streams {
+                    for stream in ads::streams(path) {
+                        match std::fs::read(&stream) {
+                            Ok(bytes) => scan(&stream, &bytes),
+                            Err(e) => errors.report(tx, Some(stream.clone()), ErrorSource::File, e.to_string()),
+                        }
+                    }
+                }
+            }
+        }
+
+        name_hit || content_hits > 0
+    }
+
+    // true when dedup_hardlinks is on and another link to this file was already searched
+    fn seen_link(&self, path: &Path) -> bool {
+        if !self.options.dedup_hardlinks {
+            return false;
+        }
+        match file_id::hardlink_id(path) {
+            Some(id) => !self.seen_links.lock().unwrap().insert(id),
+            None => false,
+        }
+    }
+
+    fn is_gzip(&self, path: &Path) -> bool {
+        self.options.search_gzip && path.extension().is_some_and(|e| e.eq_ignore_ascii_case("gz"))
+    }
+
+    // Sends every hit in the file to the sink and returns how many there were
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(path = %path.display(), len = mmap.len())))]
+    fn process_file_content<S: Sink>(&self, path: &Path, mmap: &[u8], encoding: Option<&'static str>, matcher: &TextMatcher, tx: &S, cancel: &AtomicBool) -> Result<usize, SearchError> {
+        // scanning starts right after the skipped lines, a file shorter than that has nothing left
+        let scan_start = match self.options.skip_first_lines {
+            0 => 0,
+            n => memchr_iter(b'\n', mmap).nth(n - 1).map(|p| p + 1).unwrap_or(mmap.len()),
+        };
+
+        let mut last_counted_pos = scan_start;
+        let mut current_line_number = 1 + self.options.skip_first_lines;
+        let mut hits = 0;
+
+        let mut omitted = 0;
+
+        // where the comments are, only worked out when a scope is set. for foo.rs.gz it goes by the "rs"
+        let comments = match self.options.scope {
+            MatchScope::Anywhere => Vec::new(),
+            _ => {
+                let lang_path = if self.is_gzip(path) { Path::new(path.file_stem().unwrap_or_default()) } else { path };
+                let ext = lang_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+                scope::comment_ranges(ext, mmap)
+            }
+        };
+
+        // the section only gets looked for above the last line it was checked from,
+        // so a file full of matches is still scanned backwards once
+        let mut section: Option<&[u8]> = None;
+        let mut section_checked_to = 0;
+
+        // blamed the first time a line in the file matches
+        let blame_lines = self.options.blame && !self.is_gzip(path) && self.options.extractors.get(path).is_none();
+        let mut blame: Option<Option<Vec<BlameInfo>>> = None;
+
+        // with MatchMode::All, which terms have turned up so far and the matches waiting on the rest
+        let terms = 1 + self.options.more_text_queries.len();
+        let mut seen = vec![false; terms];
+        let mut missing = if self.options.match_mode == MatchMode::All { terms } else { 0 };
+        let mut held = Vec::new();
+
+        for (i, (pattern, Range { start: match_start, end: match_end })) in matcher.find_iter(mmap, scan_start).enumerate() {
+            // a file with millions of matches could outlast the deadline (or a cancel) by itself
+            if i % 1024 == 1023 && (self.out_of_time() || cancel.load(Ordering::Relaxed)) {
+                break;
+            }
+
+            let (newlines, line_bytes, line_range) = extract_line(mmap, last_counted_pos, match_start);
+            current_line_number += newlines;
+            last_counted_pos = match_start;
+            let line_start = line_range.start;
+
+            // matches come in order, so once past the last line the rest of the file can't have any
+            if let Some((first, last)) = self.options.line_range {
+                if current_line_number > last {
+                    break;
+                }
+                if current_line_number < first {
+                    continue;
+                }
+            }
+
+            // whole line mode only keeps the match if it is the entire line, give or take surrounding whitespace
+            if self.options.whole_line {
+                let lead = line_bytes.len() - line_bytes.trim_ascii_start().len();
+                let trimmed_len = line_bytes.trim_ascii().len();
+                if match_start != line_start + lead || match_end != line_start + lead + trimmed_len {
+                    continue;
+                }
+            }
+
+            if self.options.whole_word && !is_whole_word(mmap, match_start, match_end) {
+                continue;
+            }
+
+            // prefix mode only keeps matches that start the line (or start its text, with the trim variant)
+            if self.options.line_prefix {
+                let lead = if self.options.line_prefix_trim {
+                    line_bytes.len() - line_bytes.trim_ascii_start().len()
+                } else {
+                    0
+                };
+                if match_start != line_start + lead {
+                    continue;
+                }
+            }
+
+            let wanted = match self.options.scope {
+                MatchScope::Anywhere => true,
+                MatchScope::CommentsOnly => scope::in_comment(&comments, match_start),
+                MatchScope::CodeOnly => !scope::in_comment(&comments, match_start),
+            };
+            if !wanted {
+                continue;
+            }
+            if missing > 0 && !std::mem::replace(&mut seen[pattern], true) {
+                missing -= 1;
+            }
+
+            // past the cap the rest only get counted for the "and N more" note
+            if self.options.max_matches_per_file.is_some_and(|cap| hits >= cap) {
+                omitted += 1;
+                continue;
+            }
+
+            let (text, truncated, span) = if self.options.only_matching {
+                // just the matched text, the columns still say where it sits in the line
+                (&mmap[match_start..match_end], false, (match_start - line_start, match_end - line_start))
+            } else {
+                let (line_bytes, truncated) = match self.options.max_line_len {
+                    Some(max) if line_bytes.len() > max => (&line_bytes[..floor_char_boundary(line_bytes, max)], true),
+                    _ => (line_bytes, false),
+                };
+                // a match running past a truncated line gets clamped to what's left of it
+                let span_start = (match_start - line_start).min(line_bytes.len());
+                let span_end = (match_end - line_start).min(line_bytes.len());
+                (line_bytes, truncated, (span_start, span_end))
+            };
+
+            if let Some(ref marker) = self.section_marker {
+                if let Some(found) = find_section(&mmap[section_checked_to..line_start], marker) {
+                    section = Some(match self.options.max_line_len {
+                        Some(max) if found.len() > max => &found[..floor_char_boundary(found, max)],
+                        _ => found,
+                    });
+                }
+                section_checked_to = line_start;
+            }
+
+            let blamed = if blame_lines {
+                blame.get_or_insert_with(|| git::blame(path)).as_ref().and_then(|lines| lines.get(current_line_number - 1))
+            } else {
+                None
+            };
+
+            let (context_before, context_after) = match self.options.context {
+                0 => (Vec::new(), Vec::new()),
+                n => {
+                    let (mut before, mut after) = context_lines(mmap, line_range.clone(), n);
+                    if let Some(max) = self.options.max_line_len {
+                        for line in before.iter_mut().chain(after.iter_mut()).filter(|l| l.len() > max) {
+                            *line = &line[..floor_char_boundary(line, max)];
+                        }
+                    }
+                    (before, after)
+                }
+            };
+
+            let numbering = self.options.numbering;
+            let hit = LineHit {
+                line_number: numbering.line(current_line_number),
+                line: text,
+                truncated,
+                pattern,
+                span,
+                column: numbering.column(span.0),
+                section,
+                hash: self.options.include_hash.then(|| match_hash(path, current_line_number, text)),
+                encoding,
+                raw_line: self.options.include_raw_line.then(|| {
+                    let end = memchr(b'\n', &mmap[line_range.end..]).map_or(mmap.len(), |p| line_range.end + p + 1);
+                    &mmap[line_start..end]
+                }),
+                blame: blamed,
+                context_before,
+                context_after,
+            };
+            if missing > 0 {
+                held.push(content_result(path, hit));
+            } else {
+                held.drain(..).for_each(|result| tx.emit(result));
+                tx.content_match(path, hit);
+            }
+            hits += 1;
+        }
+
+        // a file that never got every term counts as no match at all
+        if missing > 0 {
+            return Ok(0);
+        }
+        held.into_iter().for_each(|result| tx.emit(result));
+        if omitted > 0 {
+            tx.emit(SearchResult::MoreMatches { path: path.to_path_buf(), omitted });
+        }
+        Ok(hits)
+    }
+}
+
+// Keeps count of unreadable entries so they can be summarized once the search is done
+struct ErrorCounts {
+    reporting: ErrorReporting,
+    dir_errors: AtomicUsize,
+    file_errors: AtomicUsize,
+}
+
+impl ErrorCounts {
+    fn new(reporting: ErrorReporting) -> Self {
+        ErrorCounts {
+            reporting,
+            dir_errors: AtomicUsize::new(0),
+            file_errors: AtomicUsize::new(0),
+        }
+    }
+
+    // counts an unreadable entry and passes it on when every error is wanted
+    fn report<S: Sink>(&self, tx: &S, path: Option<PathBuf>, source: ErrorSource, message: String) {
+        match source {
+            ErrorSource::Directory => self.dir_errors.fetch_add(1, Ordering::Relaxed),
+            ErrorSource::File => self.file_errors.fetch_add(1, Ordering::Relaxed),
+        };
+        if self.reporting == ErrorReporting::PerEntry {
+            tx.emit(SearchResult::Error { path, source, message });
+        }
+    }
+
+    fn finish<S: Sink>(self, tx: &S) {
+        let dir_errors = self.dir_errors.into_inner();
+        let file_errors = self.file_errors.into_inner();
+        if self.reporting != ErrorReporting::Silent && (dir_errors > 0 || file_errors > 0) {
+            tx.emit(SearchResult::ErrorSummary { dir_errors, file_errors });
+        }
+    }
+}
+
+// digs the path out of a walker error, they come wrapped in depth/line info
+fn error_path(err: &ignore::Error) -> Option<&Path> {
+    match err {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => error_path(err),
+        ignore::Error::Loop { child, .. } => Some(child),
+        ignore::Error::Partial(errs) => errs.iter().find_map(error_path),
+        _ => None,
+    }
+}
+
+// Finds the line a match starting at match_start sits on. Returns how many newlines come between
+// counted_from and the match (for keeping a running line number), the line itself without its
+// \n or \r\n, and where that line sits in bytes
+fn extract_line(bytes: &[u8], counted_from: usize, match_start: usize) -> (usize, &[u8], std::ops::Range<usize>) {
+    let newlines = bytecount::count(&bytes[counted_from..match_start], b'\n');
+    let start = memrchr(b'\n', &bytes[..match_start]).map(|p| p + 1).unwrap_or(0);
+    let mut end = memchr(b'\n', &bytes[match_start..]).map(|p| match_start + p).unwrap_or(bytes.len());
+    if end > start && bytes[end - 1] == b'\r' {
+        end -= 1;
+    }
+    (newlines, &bytes[start..end], start..end)
+}
+
+// Up to n lines before and after the line at line (start to end, without its line ending), stopping
+// at the ends of bytes. Each comes without its \n or \r\n, the ones before are in file order too
+fn context_lines(bytes: &[u8], line: std::ops::Range<usize>, n: usize) -> (Vec<&[u8]>, Vec<&[u8]>) {
+
+    let mut before = Vec::with_capacity(n);
+    let mut start = line.start;
+    while before.len() < n && start > 0 {
+        // start - 1 is the \n ending the line above
+        let above = memrchr(b'\n', &bytes[..start - 1]).map_or(0, |p| p + 1);
+        before.push(without_cr(&bytes[above..start - 1]));
+        start = above;
+    }
+    before.reverse();
+
+    let mut after = Vec::with_capacity(n);
+    let mut next = memchr(b'\n', &bytes[line.end..]).map_or(bytes.len(), |p| line.end + p + 1);
+    while after.len() < n && next < bytes.len() {
+        let end = memchr(b'\n', &bytes[next..]).map_or(bytes.len(), |p| next + p);
+        after.push(without_cr(&bytes[next..end]));
+        next = end + 1;
+    }
+    (before, after)
+}
+
+fn without_cr(line: &[u8]) -> &[u8] {
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+// A literal query as a case insensitive regex. The regex engine folds case one character to one
+// character, so the few letters whose case variants that misses get spelled out: ß is also ẞ or ss
+// (SS, Ss... with the case folding), the Turkish İ is also i (on its own or with a combining dot
+// above) and ı is also I
+pub(crate) fn escape_folding(query: &str) -> String {
+    let mut pattern = String::with_capacity(query.len());
+    for c in query.chars() {
+        match c {
+            'ß' | 'ẞ' => pattern.push_str("(?:ß|ẞ|ss)"),
+            'İ' => pattern.push_str("(?:İ|(?-i:i)\u{307}?)"),
+            'ı' => pattern.push_str("(?:ı|(?-i:I))"),
+            c => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    pattern
+}
+
+// The query as a regex: each word escaped, the whitespace between them turned into "one or more of
+// anything but a newline that counts as whitespace"
+fn flexible_whitespace(query: &str) -> String {
+    let words: Vec<String> = query.split_whitespace().map(regex::escape).collect();
+    if words.is_empty() {
+        return r"[^\S\n]+".to_string();
+    }
+    words.join(r"[^\S\n]+")
+}
+
+// The name decode_bom gives out that's spelled like name, for reading a decoded ContentMatch::encoding back
+#[cfg(feature = "serde")]
+fn encoding_named(name: &str) -> Option<&'static str> {
+    ["UTF-8", "UTF-16LE", "UTF-16BE"].into_iter().find(|known| *known == name)
+}
+
+// Recognizes a byte order mark and returns the encoding's name with the text as UTF-8, without the mark.
+// None when there isn't one
+fn decode_bom(bytes: &[u8]) -> Option<(&'static str, Cow<'_, [u8]>)> {
+    if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        return Some(("UTF-8", Cow::Borrowed(rest)));
+    }
+    let (encoding, unit): (_, fn([u8; 2]) -> u16) = match bytes {
+        [0xFF, 0xFE, ..] => ("UTF-16LE", u16::from_le_bytes),
+        [0xFE, 0xFF, ..] => ("UTF-16BE", u16::from_be_bytes),
+        _ => return None,
+    };
+    let units: Vec<u16> = bytes[2..].chunks_exact(2).map(|pair| unit([pair[0], pair[1]])).collect();
+    Some((encoding, Cow::Owned(String::from_utf16_lossy(&units).into_bytes())))
+}
+
+// ASCII text is the same in every normal form so that's passed through untouched, anything else is
+// decoded as UTF-8 (invalid bytes become replacement characters) and normalized
+fn normalize(bytes: &[u8], form: Normalization) -> Cow<'_, [u8]> {
+    use unicode_normalization::UnicodeNormalization;
+
+    if bytes.is_ascii() {
+        return Cow::Borrowed(bytes);
+    }
+    let text = String::from_utf8_lossy(bytes);
+    let normalized: String = match form {
+        Normalization::Nfc => text.nfc().collect(),
+        Normalization::Nfd => text.nfd().collect(),
+    };
+    Cow::Owned(normalized.into_bytes())
+}
+
+// canonicalized, or for something that doesn't exist yet its canonicalized folder plus the name
+fn absolute_path(path: &Path) -> Option<PathBuf> {
+    if let Ok(path) = path.canonicalize() {
+        return Some(path);
+    }
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    Some(parent.canonicalize().ok()?.join(path.file_name()?))
+}
+
+// file_id::Fnv over the path, line number and text
+fn match_hash(path: &Path, line_number: usize, text: &[u8]) -> u64 {
+    let mut hash = file_id::Fnv::new();
+    hash.feed(path.as_os_str().as_encoded_bytes());
+    // a separator that can't show up in a path so "a" + "1b" and "a1" + "b" come out different
+    hash.feed(&[0]);
+    hash.feed(&(line_number as u64).to_le_bytes());
+    hash.feed(text);
+    hash.0
+}
+
+// walks the lines of text from the bottom up and returns the first that matches the marker, trimmed
+fn find_section<'a>(text: &'a [u8], marker: &Regex) -> Option<&'a [u8]> {
+    let mut end = text.len();
+    while end > 0 {
+        let start = memrchr(b'\n', &text[..end - 1]).map(|p| p + 1).unwrap_or(0);
+        let line = &text[start..end];
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if marker.is_match(line) {
+            return Some(line.trim_ascii());
+        }
+        end = start;
+    }
+    None
+}
+
+// true when bytes[start..end] doesn't run into a word on either side, for whole_word. A non-ASCII
+// byte counts as part of a word so ä or 字 next to the match isn't a boundary
+pub(crate) fn is_whole_word(bytes: &[u8], start: usize, end: usize) -> bool {
+    let word = |b: &u8| b.is_ascii_alphanumeric() || *b == b'_' || !b.is_ascii();
+    !bytes[..start].last().is_some_and(word) && !bytes.get(end).is_some_and(word)
+}
+
+// backs max off until it lands on the start of a utf-8 character so a cut never splits one
+fn floor_char_boundary(bytes: &[u8], max: usize) -> usize {
+    let mut end = max.min(bytes.len());
+    while end > 0 && end < bytes.len() && (bytes[end] & 0b1100_0000) == 0b1000_0000 {
+        end -= 1;
+    }
+    end
+}
+
+// A file's bytes, either mapped straight from disk or read/decompressed into memory
+enum Contents {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Contents {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Contents::Mapped(mmap) => mmap,
+            Contents::Owned(bytes) => bytes,
+        }
+    }
+}
+
+// The portable read path, every file gets memory mapped. One that says it's empty (files in /proc
+// and /sys do, whatever's in them) or that can't be mapped gets read the plain way instead
+#[cfg(not(all(feature = "small-file-reads", target_os = "linux")))]
+fn load_file(path: &Path, max_len: Option<u64>) -> std::io::Result<Contents> {
+    let file = File::open(path)?;
+    if file.metadata()?.len() > 0
+        && let Ok(mmap) = unsafe { Mmap::map(&file) }
+    {
+        return Ok(Contents::Mapped(mmap));
+    }
+    read_file(file, max_len).map(Contents::Owned)
+}
+
+// reads the whole file without going by its size, none of it when there turns out to be more than
+// max_len (max_file_size), the same as a file whose size said so up front
+fn read_file(file: File, max_len: Option<u64>) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    file.take(max_len.map_or(u64::MAX, |max| max + 1)).read_to_end(&mut bytes)?;
+    if max_len.is_some_and(|max| bytes.len() as u64 > max) {
+        bytes = Vec::new();
+    }
+    Ok(bytes)
+}
+
+// Files at or under this size get a plain read with small-file-reads
+#[cfg(all(feature = "small-file-reads", target_os = "linux"))]
+const SMALL_FILE_LEN: u64 = 64 * 1024;
+
+// The read path with --features small-file-reads (linux only). Trees of many small files spend
+// most of their time in open+mmap+munmap, so small files are read in one go instead, and bigger
+// ones are still mapped but the kernel is told to read ahead within the file before the scan
+// reaches it. Files are still loaded one at a time on the walker's threads, nothing reads ahead of
+// the walk. If the kernel rejects the advice the map is just used as is, one that can't be mapped
+// at all gets read like a small file. tests::read_path_benchmark compares the two paths
+#[cfg(all(feature = "small-file-reads", target_os = "linux"))]
+fn load_file(path: &Path, max_len: Option<u64>) -> std::io::Result<Contents> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+
+    if len <= SMALL_FILE_LEN {
+        return read_file(file, max_len).map(Contents::Owned);
+    }
+
+    match unsafe { Mmap::map(&file) } {
+        Ok(mmap) => {
+            let _ = mmap.advise(memmap2::Advice::Sequential);
+            let _ = mmap.advise(memmap2::Advice::WillNeed);
+            Ok(Contents::Mapped(mmap))
+        }
+        Err(_) => read_file(file, max_len).map(Contents::Owned),
+    }
+}
+
+// Inflates a whole .gz into memory (nothing is written to disk), handles multi-member
+// files like the ones logrotate makes
+fn read_gzip(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    flate2::read::MultiGzDecoder::new(std::io::BufReader::new(File::open(path)?)).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+// How many bytes from the start of a file get looked at to decide if it's text
+const TEXT_PROBE_LEN: usize = 8 * 1024;
+
+// Decides if a sample from the start of a file is text: no NUL bytes and barely any
+// control characters besides the usual whitespace. An empty sample counts as text
+pub fn is_text_sample(sample: &[u8]) -> bool {
+    let sample = &sample[..TEXT_PROBE_LEN.min(sample.len())];
+    let sample = sample.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(sample);
+
+    if memchr(0, sample).is_some() {
+        return false;
+    }
+
+    let control = sample
+        .iter()
+        .filter(|&&b| (b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b)) || b == 0x7f)
+        .count();
+
+    // allows a few stray control bytes, anything past 10% is treated as binary
+    control * 10 <= sample.len()
+}
+
+// Decides if a whole file's contents are text before they get scanned. Files up to TEXT_PROBE_LEN
+// are checked in full, bigger ones by their start, middle and end (TEXT_PROBE_LEN in all), so a
+// binary with a clean header or a text-looking tail still gets caught. Each part has to pass
+// is_text_sample on its own
+pub fn looks_like_text(bytes: &[u8]) -> bool {
+    if bytes.len() <= TEXT_PROBE_LEN {
+        return is_text_sample(bytes);
+    }
+    let head = TEXT_PROBE_LEN / 2;
+    let part = TEXT_PROBE_LEN / 4;
+    let middle = bytes.len() / 2 - part / 2;
+    is_text_sample(&bytes[..head])
+        && is_text_sample(&bytes[middle..middle + part])
+        && is_text_sample(&bytes[bytes.len() - part..])
+}
+
+// reads just the probe window so name-only searches don't have to map the whole file
+fn sniff_is_text(path: &Path) -> bool {
+    let mut sample = Vec::with_capacity(TEXT_PROBE_LEN);
+    match File::open(path).and_then(|file| file.take(TEXT_PROBE_LEN as u64).read_to_end(&mut sample)) {
+        Ok(_) => is_text_sample(&sample),
+        Err(_) => false,
+    }
+}
+
+// dotfiles everywhere, plus anything with the hidden attribute on windows
+fn is_hidden(path: &Path) -> bool {
+    if path.file_name().is_some_and(|n| n.as_encoded_bytes().starts_with(b".")) {
+        return true;
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0) {
+            return true;
+        }
+    }
+    false
+}
+
+// The depth adaptive_depth caps the walk at, only for a slow root and when max_depth wasn't set
+fn adaptive_max_depth(options: &SearchOptions, slow_root: bool) -> Option<usize> {
+    (options.adaptive_depth && options.max_depth.is_none() && slow_root).then_some(ADAPTIVE_MAX_DEPTH)
+}
+
+// The kernel's pseudo filesystems, only when they're mounted at the top of the filesystem
+#[cfg(unix)]
+fn is_system_path(path: &Path) -> bool {
+    matches!(path.to_str(), Some("/proc" | "/sys" | "/dev"))
+}
+
+#[cfg(not(unix))]
+fn is_system_path(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, sync::mpsc};
+
+    fn options(root: &Path, query: &str) -> SearchOptions {
+        SearchOptions { root: root.display().to_string(), text_query: Some(query.to_string()), ..SearchOptions::default() }
+    }
+
+    fn content_lines(results: &[SearchResult]) -> Vec<usize> {
+        results.iter().filter_map(|r| match r {
+            SearchResult::ContentMatch { line_number, .. } => Some(*line_number),
+            _ => None,
+        }).collect()
+    }
+
+    fn search(options: SearchOptions) -> Vec<SearchResult> {
+        let (tx, rx) = mpsc::channel();
+        run_search(options, tx, Arc::new(AtomicBool::new(false))).unwrap();
+        rx.into_iter().collect()
+    }
+
+    // the names of the files that matched by name, sorted
+    fn file_names(results: &[SearchResult]) -> Vec<String> {
+        let mut names: Vec<_> = results.iter().filter_map(|r| match r {
+            SearchResult::FileNameMatch { path, .. } => Some(path.file_name()?.to_string_lossy().into_owned()),
+            _ => None,
+        }).collect();
+        names.sort();
+        names
+    }
+
+    // the names of the files with a content match, sorted, once per match
+    fn content_files(results: &[SearchResult]) -> Vec<String> {
+        let mut names: Vec<_> = results.iter().filter_map(|r| match r {
+            SearchResult::ContentMatch { path, .. } => Some(path.file_name()?.to_string_lossy().into_owned()),
+            _ => None,
+        }).collect();
+        names.sort();
+        names
+    }
+
+    fn search_one(path: &Path, options: SearchOptions, cancelled: bool) -> Vec<SearchResult> {
+        let (tx, rx) = mpsc::channel();
+        search_file(path, options, tx, Arc::new(AtomicBool::new(cancelled))).unwrap();
+        rx.into_iter().collect()
+    }
+
+    #[test]
+    fn fixed_string_takes_a_regex_query_literally() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "a.b\naxb\n").unwrap();
+
+        let fixed = search(SearchOptions { regex: true, fixed_string: true, ..options(dir.path(), "a.b") });
+        let regex = search(SearchOptions { regex: true, ..options(dir.path(), "a.b") });
+
+        assert_eq!(content_lines(&fixed), [1]);
+        assert_eq!(content_lines(&regex), [1, 2]);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_lines_load_back_as_they_were_written() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "one hit\nnone\n\"hit\", two\n").unwrap();
+
+        let mut written = Vec::new();
+        run_search_json(options(dir.path(), "hit"), &mut written, Arc::new(AtomicBool::new(false))).unwrap();
+        // a line cut off halfway gets skipped and counted
+        written.extend_from_slice(b"{\"type\":\"content_ma");
+        let (loaded, skipped) = load_results_json(written.as_slice()).unwrap();
+
+        let sorted = |results: &[SearchResult]| {
+            let mut lines: Vec<String> = results.iter().map(|r| format!("{:?}", r)).collect();
+            lines.sort();
+            lines
+        };
+        assert_eq!(skipped, 1);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(sorted(&loaded), sorted(&search(options(dir.path(), "hit"))));
+    }
+
+    #[test]
+    fn search_file_keeps_to_max_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "hit\n".repeat(10)).unwrap();
+
+        let results = search_one(&file, SearchOptions { max_results: Some(3), ..options(dir.path(), "hit") }, false);
+
+        assert_eq!(content_lines(&results), [1, 2, 3]);
+    }
+
+    #[test]
+    fn search_file_stops_when_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "hit\n".repeat(5000)).unwrap();
+
+        let all = search_one(&file, options(dir.path(), "hit"), false);
+        let cancelled = search_one(&file, options(dir.path(), "hit"), true);
+
+        assert_eq!(content_lines(&all).len(), 5000);
+        // the flag is looked at every 1024 matches
+        assert!(content_lines(&cancelled).len() < 1024);
+    }
+
+    // the same terms as literals and as a regex that only has literals in it
+    #[test]
+    fn literal_and_regex_terms_match_the_same_spans() {
+        let terms = SearchOptions { more_text_queries: vec!["foo".into(), "bar".into()], ..options(Path::new("."), "foobar") };
+        let literal = TextMatcher::new(&terms).unwrap().unwrap();
+        let regex = TextMatcher::new(&SearchOptions { regex: true, ..terms }).unwrap().unwrap();
+        assert!(matches!(literal, TextMatcher::Literal(_)));
+        assert!(matches!(regex, TextMatcher::Regex { .. }));
+
+        let haystack = b"foobar foo barfoo";
+        let spans = |m: &TextMatcher| m.find_iter(haystack, 0).collect::<Vec<_>>();
+        assert_eq!(spans(&literal), [(0, 0..6), (1, 7..10), (2, 11..14), (1, 14..17)]);
+        assert_eq!(spans(&literal), spans(&regex));
+    }
+
+    #[test]
+    fn dropping_the_bounded_receiver_stops_the_search() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            fs::write(dir.path().join(format!("{i}.txt")), "hit\n".repeat(100)).unwrap();
+        }
+        let (tx, rx) = mpsc::sync_channel(1);
+        let token = Arc::new(AtomicBool::new(false));
+
+        let search = std::thread::spawn({
+            let (options, token) = (options(dir.path(), "hit"), token.clone());
+            move || run_search_bounded(options, tx, token)
+        });
+        rx.recv().unwrap();
+        drop(rx);
+
+        search.join().unwrap().unwrap();
+        assert!(token.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn text_samples() {
+        assert!(is_text_sample(b""));
+        assert!(is_text_sample(b"short"));
+        assert!(is_text_sample("\u{feff}caf\u{e9}\tline\r\n".as_bytes()));
+        // just the BOM is an empty file
+        assert!(is_text_sample(b"\xEF\xBB\xBF"));
+        assert!(!is_text_sample(b"ab\0cd"));
+        assert!(!is_text_sample(b"\x01\x02\x03 text"));
+        // only the probe window is looked at
+        let mut late_nul = vec![b'a'; TEXT_PROBE_LEN];
+        late_nul.push(0);
+        assert!(is_text_sample(&late_nul));
+    }
+
+    // contents get the same check either way, text_only is what keeps binaries out of name matches
+    #[test]
+    fn text_only_skips_binary_files_without_an_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("notes"), "text\n").unwrap();
+        // no NUL in it, so only the control bytes give it away
+        fs::write(dir.path().join("notes-tool"), b"\x01\x02\x03\x04text\n").unwrap();
+        let names = |text_only| {
+            let options = SearchOptions { root: dir.path().display().to_string(), file_query: Some("notes".into()), text_only, ..SearchOptions::default() };
+            file_names(&search(options))
+        };
+
+        assert_eq!(names(false), ["notes", "notes-tool"]);
+        assert_eq!(names(true), ["notes"]);
+    }
+
+    #[test]
+    fn options_from_vars() {
+        let vars: HashMap<&str, &str> = [
+            ("FASTSEARCH_THREADS", " 4 "),
+            ("FASTSEARCH_MAX_DEPTH", "deep"),
+            ("FASTSEARCH_FILE_TYPES", "rs,toml"),
+            ("FASTSEARCH_IGNORE_CASE", "Yes"),
+            ("FASTSEARCH_TEXT_ONLY", "0"),
+            ("FASTSEARCH_ERRORS", "each"),
+        ].into();
+        let options = SearchOptions::from_vars(|key| vars.get(key).map(|v| v.to_string()));
+
+        assert_eq!(options.threads, Some(4));
+        // didn't parse, so the built-in default stays
+        assert_eq!(options.max_depth, None);
+        assert_eq!(options.file_types.as_deref(), Some("rs,toml"));
+        assert!(options.ignore_case);
+        assert!(!options.text_only);
+        assert_eq!(options.error_reporting, ErrorReporting::PerEntry);
+    }
+
+    #[test]
+    fn explicit_options_win_over_the_environment() {
+        // SAFETY: no other test reads or writes this variable
+        unsafe { std::env::set_var("FASTSEARCH_FILE_TYPES", "md") };
+        let from_env = SearchOptions::from_env();
+        let explicit = SearchOptions { file_types: Some("rs".into()), ..SearchOptions::from_env() };
+        unsafe { std::env::remove_var("FASTSEARCH_FILE_TYPES") };
+
+        assert_eq!(from_env.file_types.as_deref(), Some("md"));
+        assert_eq!(explicit.file_types.as_deref(), Some("rs"));
+        assert_eq!(SearchOptions::from_env().file_types, None);
+    }
+
+    #[test]
+    fn max_matches_per_file_caps_each_file_on_its_own() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("many.txt"), "hit\n".repeat(10)).unwrap();
+        fs::write(dir.path().join("few.txt"), "hit\nhit\n").unwrap();
+
+        let results = search(SearchOptions { max_matches_per_file: Some(3), ..options(dir.path(), "hit") });
+
+        let from = |name: &str| results.iter().filter(|r| matches!(r, SearchResult::ContentMatch { path, .. } if path.ends_with(name))).count();
+        assert_eq!((from("many.txt"), from("few.txt")), (3, 2));
+        let more: Vec<_> = results.iter().filter_map(|r| match r {
+            SearchResult::MoreMatches { path, omitted } => Some((path.file_name().unwrap().to_owned(), *omitted)),
+            _ => None,
+        }).collect();
+        assert_eq!(more, [("many.txt".into(), 7)]);
+    }
+
+    #[test]
+    fn repo_and_parent_gitignores_toggle_separately() {
+        let repo = tempfile::tempdir().unwrap();
+        let sub = repo.path().join("sub");
+        fs::create_dir_all(repo.path().join(".git")).unwrap();
+        fs::create_dir(&sub).unwrap();
+        fs::write(repo.path().join(".gitignore"), "parent.txt\n").unwrap();
+        fs::write(sub.join(".gitignore"), "local.txt\n").unwrap();
+        for name in ["kept.txt", "parent.txt", "local.txt"] {
+            fs::write(sub.join(name), "").unwrap();
+        }
+        let names = |respect_gitignore, respect_parent_ignores| {
+            let options = SearchOptions {
+                root: sub.display().to_string(),
+                file_query: Some(".txt".into()),
+                respect_gitignore,
+                respect_parent_ignores,
+                ..SearchOptions::default()
+            };
+            file_names(&search(options))
+        };
+
+        assert_eq!(names(true, true), ["kept.txt"]);
+        assert_eq!(names(true, false), ["kept.txt", "parent.txt"]);
+        assert_eq!(names(false, false), ["kept.txt", "local.txt", "parent.txt"]);
+    }
+
+    #[test]
+    fn gzip_is_searched_without_temp_files() {
+        use std::io::Write;
+        let dir = tempfile::tempdir().unwrap();
+        let mut gz = flate2::write::GzEncoder::new(File::create(dir.path().join("app.log.gz")).unwrap(), flate2::Compression::default());
+        gz.write_all(b"ok\nhit here\n").unwrap();
+        gz.finish().unwrap();
+
+        let results = search(SearchOptions { search_gzip: true, ..options(dir.path(), "hit") });
+
+        assert_eq!(content_lines(&results), [2]);
+        // nothing got unpacked next to it
+        let names: Vec<_> = fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, ["app.log.gz"]);
+    }
+
+    #[test]
+    fn truncated_only_when_max_line_len_cuts_the_line() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "hit\nhit and a lot more\nhit \u{e9}\u{e9}\n").unwrap();
+
+        let results = search(SearchOptions { max_line_len: Some(5), ..options(dir.path(), "hit") });
+
+        let mut lines: Vec<_> = results.iter().filter_map(|r| match r {
+            SearchResult::ContentMatch { line_number, line_text, truncated, .. } => Some((*line_number, line_text.as_str(), *truncated)),
+            _ => None,
+        }).collect();
+        lines.sort();
+        // the last one is cut before the é it would have split
+        assert_eq!(lines, [(1, "hit", false), (2, "hit a", true), (3, "hit ", true)]);
+    }
+
+    #[test]
+    fn whole_line_needs_the_line_to_be_the_query() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "hit\n  hit \nhit me\nHIT\nhat\n").unwrap();
+        let lines = |options: SearchOptions| {
+            let mut lines = content_lines(&search(SearchOptions { whole_line: true, ..options }));
+            lines.sort();
+            lines
+        };
+
+        assert_eq!(lines(options(dir.path(), "hit")), [1, 2]);
+        assert_eq!(lines(SearchOptions { text_ignore_case: true, ..options(dir.path(), "hit") }), [1, 2, 4]);
+        assert_eq!(lines(SearchOptions { regex: true, ..options(dir.path(), "h.t") }), [1, 2, 5]);
+    }
+
+    #[test]
+    fn recycle_bins_and_trash_are_skipped_unless_search_all() {
+        let dir = tempfile::tempdir().unwrap();
+        for folder in ["$RECYCLE.BIN", "System Volume Information", ".Trash-1000", ".Trashes", "Trash", "kept"] {
+            fs::create_dir(dir.path().join(folder)).unwrap();
+            fs::write(dir.path().join(folder).join("found.txt"), "").unwrap();
+        }
+        let folders = |search_all| {
+            let options = SearchOptions { root: dir.path().display().to_string(), file_query: Some("found".into()), search_all, ..SearchOptions::default() };
+            let mut folders: Vec<_> = search(options).into_iter().filter_map(|r| match r {
+                SearchResult::FileNameMatch { path, .. } => Some(path.parent()?.file_name()?.to_string_lossy().into_owned()),
+                _ => None,
+            }).collect();
+            folders.sort();
+            folders
+        };
+
+        assert_eq!(folders(false), ["Trash", "kept"]);
+        assert_eq!(folders(true).len(), 6);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kernel_folders_only_at_the_top() {
+        assert!(is_system_path(Path::new("/proc")));
+        assert!(is_system_path(Path::new("/dev")));
+        assert!(!is_system_path(Path::new("/home/me/proc")));
+        assert!(!is_system_path(Path::new("/devices")));
+    }
+
+    #[test]
+    fn line_prefix_only_at_the_start() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "import x\n    import y\nre-import z\n\timport w\n").unwrap();
+        let lines = |line_prefix_trim| {
+            let mut lines = content_lines(&search(SearchOptions { line_prefix: true, line_prefix_trim, ..options(dir.path(), "import") }));
+            lines.sort();
+            lines
+        };
+
+        assert_eq!(lines(false), [1]);
+        assert_eq!(lines(true), [1, 2, 4]);
+    }
+
+    #[test]
+    fn max_duration_stops_with_partial_results() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..200 {
+            fs::write(dir.path().join(format!("{i}.txt")), "hit\n").unwrap();
+        }
+
+        let started = Instant::now();
+        let results = search(SearchOptions { max_duration: Some(Duration::ZERO), ..options(dir.path(), "hit") });
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(content_lines(&results).len() < 200);
+        assert!(matches!(results.last(), Some(SearchResult::TimedOut)));
+        assert_eq!(content_lines(&search(options(dir.path(), "hit"))).len(), 200);
+    }
+
+    #[test]
+    fn sections_from_a_rust_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = "// todo up top\nuse std::io;\n\nfn parse() {\n    let x = todo();\n}\n\nimpl S {\n    fn render(&self) {\n        // todo\n    }\n}\n";
+        fs::write(dir.path().join("lib.rs"), source).unwrap();
+        let sections = |marker: &str| {
+            let mut sections: Vec<_> = search(SearchOptions { section_marker: Some(marker.into()), ..options(dir.path(), "todo") })
+                .into_iter()
+                .filter_map(|r| match r {
+                    SearchResult::ContentMatch { line_number, section, .. } => Some((line_number, section)),
+                    _ => None,
+                })
+                .collect();
+            sections.sort();
+            sections
+        };
+
+        // the marker line comes back trimmed
+        assert_eq!(sections(DEFAULT_SECTION_MARKER), [
+            (1, None),
+            (5, Some("fn parse() {".to_string())),
+            (10, Some("fn render(&self) {".to_string())),
+        ]);
+        assert_eq!(sections(r"^impl\b"), [(1, None), (5, None), (10, Some("impl S {".to_string()))]);
+    }
+
+    #[test]
+    fn file_and_text_case_are_independent() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Readme.md"), "Hello\n").unwrap();
+        let root = dir.path().display().to_string();
+        let names = |options: SearchOptions| file_names(&search(SearchOptions { file_query: Some("readme".into()), ..options }));
+        let lines = |options: SearchOptions| content_lines(&search(SearchOptions { text_query: Some("hello".into()), ..options }));
+        let base = || SearchOptions { root: root.clone(), ..SearchOptions::default() };
+
+        assert_eq!(names(SearchOptions { file_ignore_case: true, ..base() }), ["Readme.md"]);
+        assert!(names(SearchOptions { text_ignore_case: true, ..base() }).is_empty());
+        assert_eq!(lines(SearchOptions { text_ignore_case: true, ..base() }), [1]);
+        assert!(lines(SearchOptions { file_ignore_case: true, ..base() }).is_empty());
+        // ignore_case is both
+        assert_eq!(names(SearchOptions { ignore_case: true, ..base() }), ["Readme.md"]);
+        assert_eq!(lines(SearchOptions { ignore_case: true, ..base() }), [1]);
+    }
+
+    #[test]
+    fn only_gitignored_finds_what_the_repo_ignores() {
+        let dir = tempfile::tempdir().unwrap();
+        // a folder of its own so the root's name doesn't match too
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("target/debug")).unwrap();
+        fs::write(repo.join(".gitignore"), "target/\n*.log\n!keep.log\n").unwrap();
+        for name in ["main.rs", "app.log", "keep.log", "target/debug/build.txt"] {
+            fs::write(repo.join(name), "").unwrap();
+        }
+        let names = |only_gitignored| {
+            file_names(&search(SearchOptions {
+                root: repo.display().to_string(),
+                file_query: Some(".".into()),
+                only_gitignored,
+                ..SearchOptions::default()
+            }))
+        };
+
+        assert_eq!(names(false), [".gitignore", "keep.log", "main.rs"]);
+        assert_eq!(names(true), ["app.log", "build.txt"]);
+    }
+
+    #[test]
+    fn skip_first_lines_keeps_line_numbers_absolute() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.csv"), "hit,header\nhit,units\nrow\nhit,row\n").unwrap();
+        fs::write(dir.path().join("short.csv"), "hit\n").unwrap();
+
+        let results = search(SearchOptions { skip_first_lines: 2, ..options(dir.path(), "hit") });
+
+        // short.csv is all header
+        assert_eq!(content_lines(&results), [4]);
+    }
+
+    #[test]
+    fn hardlinked_contents_are_searched_once() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "hit\n").unwrap();
+        fs::hard_link(dir.path().join("a.txt"), dir.path().join("b.txt")).unwrap();
+        fs::write(dir.path().join("copy.txt"), "hit\n").unwrap();
+        let matched = |dedup_hardlinks| {
+            content_files(&search(SearchOptions { dedup_hardlinks, ..options(dir.path(), "hit") }))
+        };
+
+        assert_eq!(matched(false), ["a.txt", "b.txt", "copy.txt"]);
+        // whichever link the walk got to first, a copy isn't a link
+        let deduped = matched(true);
+        assert_eq!(deduped.len(), 2);
+        assert_eq!(deduped[1], "copy.txt");
+    }
+
+    #[test]
+    fn only_matching_sends_each_matched_text() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "id=42 and id=7\nna\u{ef}ve caf\u{e9}\u{e9}s\n").unwrap();
+        let matched = |query: &str| {
+            search(SearchOptions { only_matching: true, regex: true, ..options(dir.path(), query) }).into_iter().filter_map(|r| match r {
+                SearchResult::ContentMatch { line_number, line_text, match_start_col, match_end_col, .. } => Some((line_number, line_text, match_start_col, match_end_col)),
+                _ => None,
+            }).collect::<Vec<_>>()
+        };
+
+        // one per match, the columns are still where it sits in the line
+        assert_eq!(matched(r"id=\d+"), [(1, "id=42".to_string(), 0, 5), (1, "id=7".to_string(), 10, 14)]);
+        assert_eq!(matched("\u{e9}+s"), [(2, "\u{e9}\u{e9}s".to_string(), 10, 15)]);
+    }
+
+    #[test]
+    fn excluded_paths_never_show_up() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("out/nested")).unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("report.txt"), "hit\n").unwrap();
+        fs::write(dir.path().join("out/nested/more.txt"), "hit\n").unwrap();
+        fs::write(dir.path().join("src/kept.txt"), "hit\n").unwrap();
+
+        let results = search(SearchOptions {
+            // written differently from how the walk comes across them
+            exclude_paths: vec![dir.path().join("src/../report.txt"), dir.path().join("out/")],
+            file_query: Some(".txt".into()),
+            combine: Combine::Or,
+            ..options(dir.path(), "hit")
+        });
+
+        let mut paths: Vec<_> = results.iter().filter_map(|r| match r {
+            SearchResult::ContentMatch { path, .. } | SearchResult::FileNameMatch { path, .. } => Some(path.strip_prefix(dir.path()).ok()?.to_path_buf()),
+            _ => None,
+        }).collect();
+        paths.sort();
+        paths.dedup();
+        assert_eq!(paths, [PathBuf::from("src/kept.txt")]);
+    }
+
+    #[test]
+    fn file_names_by_regex() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("tests")).unwrap();
+        for name in ["test_parse.rs", "my_test_parse.rs", "test_parse.rsx", "build.toml", "tests/Cargo.lock"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        let names = |query: &str, match_full_path| {
+            file_names(&search(SearchOptions {
+                root: dir.path().display().to_string(),
+                file_query: Some(query.into()),
+                file_regex: true,
+                match_full_path,
+                ..SearchOptions::default()
+            }))
+        };
+
+        assert_eq!(names(r"^test_.*\.rs$", false), ["test_parse.rs"]);
+        assert_eq!(names(r"\.(toml|lock)$", false), ["Cargo.lock", "build.toml"]);
+        // against the path from the root instead, with / between folders everywhere
+        assert_eq!(names(r"^tests/", true), ["Cargo.lock"]);
+
+        let (tx, _rx) = mpsc::channel();
+        let broken = SearchOptions { file_query: Some("(unclosed".into()), file_regex: true, ..SearchOptions::default() };
+        assert!(matches!(run_search(broken, tx, Arc::new(AtomicBool::new(false))), Err(SearchError::PatternError)));
+    }
+
+    #[test]
+    fn extract_line_at_the_ends_of_a_file() {
+        let bytes = b"first hit\nmiddle\nlast hit";
+        // a match at the very start of the file
+        assert_eq!(extract_line(bytes, 0, 0), (0, &b"first hit"[..], 0..9));
+        assert_eq!(extract_line(bytes, 0, 6), (0, &b"first hit"[..], 0..9));
+        // the last line has no newline after it
+        assert_eq!(extract_line(bytes, 0, 22), (2, &b"last hit"[..], 17..25));
+    }
+
+    #[test]
+    fn extract_line_drops_the_cr_of_crlf() {
+        let bytes = b"one\r\ntwo hit\r\n\r\nthree";
+        assert_eq!(extract_line(bytes, 0, 9), (1, &b"two hit"[..], 5..12));
+        // an empty CRLF line is empty, not a lone \r
+        assert_eq!(extract_line(bytes, 0, 14), (2, &b""[..], 14..14));
+    }
+
+    #[test]
+    fn extract_line_counts_from_the_last_match() {
+        let bytes = b"a\nb hit\nc\nd hit\n";
+        let (first, _, first_line) = extract_line(bytes, 0, 4);
+        assert_eq!((first, first_line.clone()), (1, 2..7));
+        // only the newlines between the two matches, the running line number adds it on
+        assert_eq!(extract_line(bytes, 4, 12), (2, &b"d hit"[..], 10..15));
+    }
+
+    #[test]
+    fn extract_line_with_multi_byte_text() {
+        let bytes = "caf\u{e9}\n\u{1f980} hit \u{e9}\n".as_bytes();
+        let (newlines, line, range) = extract_line(bytes, 0, 11);
+        assert_eq!((newlines, std::str::from_utf8(line).unwrap(), range), (1, "\u{1f980} hit \u{e9}", 6..17));
+    }
+
+    #[test]
+    fn hidden_only_searches_dotfiles_and_hidden_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(".config")).unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        for name in [".env", ".config/settings.toml", "src/main.rs", "README.md"] {
+            fs::write(dir.path().join(name), "token\n").unwrap();
+        }
+
+        let results = search(SearchOptions { hidden_only: true, ..options(dir.path(), "token") });
+
+        let mut paths: Vec<_> = results.iter().filter_map(|r| match r {
+            SearchResult::ContentMatch { path, .. } => Some(path.strip_prefix(dir.path()).ok()?.to_path_buf()),
+            _ => None,
+        }).collect();
+        paths.sort();
+        assert_eq!(paths, [PathBuf::from(".config/settings.toml"), PathBuf::from(".env")]);
+    }
+
+    #[test]
+    fn match_hashes_are_stable_and_tell_matches_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "hit\nhit\nother hit\n").unwrap();
+        let hashes = |include_hash| {
+            let mut hashes: Vec<_> = search(SearchOptions { include_hash, ..options(dir.path(), "hit") }).into_iter().filter_map(|r| match r {
+                SearchResult::ContentMatch { line_number, hash, .. } => Some((line_number, hash)),
+                _ => None,
+            }).collect();
+            hashes.sort();
+            hashes
+        };
+
+        let first = hashes(true);
+        assert_eq!(first, hashes(true));
+        // the same text on another line is another finding
+        assert!(first[0].1 != first[1].1 && first[1].1 != first[2].1);
+        assert!(hashes(false).iter().all(|(_, hash)| hash.is_none()));
+        // pinned, it mustn't change between versions
+        assert_eq!(match_hash(Path::new("a.txt"), 3, b"hit"), 0x733d_4d9c_dd96_dc02);
+    }
+
+    #[test]
+    fn normalized_forms_match_each_other() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("composed.txt"), "caf\u{e9}\n").unwrap();
+        fs::write(dir.path().join("decomposed.txt"), "cafe\u{301}\n").unwrap();
+        let matched = |query: &str, normalize_unicode| {
+            content_files(&search(SearchOptions { normalize_unicode, ..options(dir.path(), query) }))
+        };
+
+        assert_eq!(matched("caf\u{e9}", None), ["composed.txt"]);
+        assert_eq!(matched("caf\u{e9}", Some(Normalization::Nfc)), ["composed.txt", "decomposed.txt"]);
+        assert_eq!(matched("cafe\u{301}", Some(Normalization::Nfd)), ["composed.txt", "decomposed.txt"]);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_stream_decodes_back_into_results() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "one hit\nnone\nhit \u{e9}\n").unwrap();
+
+        let mut written = Vec::new();
+        run_search_msgpack(options(dir.path(), "hit"), &mut written, Arc::new(AtomicBool::new(false))).unwrap();
+        let mut stream = written.as_slice();
+        let mut decoded = Vec::new();
+        while !stream.is_empty() {
+            decoded.push(rmp_serde::from_read::<_, SearchResult>(&mut stream).unwrap());
+        }
+
+        let debug = |results: &[SearchResult]| results.iter().map(|r| format!("{:?}", r)).collect::<Vec<_>>();
+        assert_eq!(content_lines(&decoded), [1, 3]);
+        assert_eq!(debug(&decoded), debug(&search(options(dir.path(), "hit"))));
+    }
+
+    #[test]
+    fn max_entries_per_dir_caps_each_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let wide = dir.path().join("wide");
+        fs::create_dir(&wide).unwrap();
+        for i in 0..50 {
+            fs::write(wide.join(format!("{i}.txt")), "hit\n").unwrap();
+        }
+        fs::write(dir.path().join("top.txt"), "hit\n").unwrap();
+
+        let results = search(SearchOptions { max_entries_per_dir: Some(10), ..options(dir.path(), "hit") });
+
+        let from_wide = results.iter().filter(|r| matches!(r, SearchResult::ContentMatch { path, .. } if path.starts_with(&wide))).count();
+        assert_eq!(from_wide, 10);
+        assert_eq!(content_files(&results).iter().filter(|name| *name == "top.txt").count(), 1);
+        let warned: Vec<_> = results.iter().filter_map(|r| match r {
+            SearchResult::MoreEntries { path, omitted } => Some((path.clone(), *omitted)),
+            _ => None,
+        }).collect();
+        assert_eq!(warned, [(wide, 40)]);
+    }
+
+    #[test]
+    fn unicode_case_folds_a_literal_query() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "\u{212a}.STRASSE\nK.STRA\u{1e9e}E\nkxstra\u{df}e\nk.stra\u{df}e\nK.STRASSE\n").unwrap();
+        let mut lines = content_lines(&search(SearchOptions { text_ignore_case: true, unicode_case: true, ..options(dir.path(), "k.stra\u{df}e") }));
+        lines.sort();
+
+        // the . is still just a dot, the Kelvin sign is a k and ß is SS or ẞ
+        assert_eq!(lines, [1, 2, 4, 5]);
+    }
+
+    #[test]
+    fn unicode_case_for_an_ascii_query() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "\u{212a}elvin\nkelvin\n").unwrap();
+        let lines = |unicode_case| {
+            let mut lines = content_lines(&search(SearchOptions { text_ignore_case: true, unicode_case, ..options(dir.path(), "KELVIN") }));
+            lines.sort();
+            lines
+        };
+
+        assert_eq!(lines(false), [2]);
+        assert_eq!(lines(true), [1, 2]);
+    }
+
+    #[test]
+    fn a_tiny_load_budget_takes_files_one_at_a_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let big = "x".repeat(64 * 1024) + "hit\n";
+        for i in 0..4 {
+            fs::write(dir.path().join(format!("{i}.txt")), &big).unwrap();
+        }
+        let budgeted = || SearchOptions { max_loaded_bytes: Some(1024), threads: Some(4), ..options(dir.path(), "hit") };
+
+        // every file is over the budget, each one is still searched, on its own
+        assert_eq!(content_lines(&search(budgeted())).len(), 4);
+
+        let config = SearchConfig::new(budgeted()).unwrap();
+        let first = config.borrow_bytes(&dir.path().join("0.txt")).unwrap();
+        let (loaded, waiting) = mpsc::channel();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let _second = config.borrow_bytes(&dir.path().join("1.txt"));
+                loaded.send(()).unwrap();
+            });
+            assert!(waiting.recv_timeout(Duration::from_millis(100)).is_err());
+            drop(first);
+            waiting.recv_timeout(Duration::from_secs(5)).unwrap();
+        });
+    }
+
+    #[test]
+    fn flexible_whitespace_matches_any_run_of_it() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "a\tb\na  b\nab\na.b\nfn   main(\n").unwrap();
+        let lines = |query: &str| {
+            let mut lines = content_lines(&search(SearchOptions { flexible_whitespace: true, ..options(dir.path(), query) }));
+            lines.sort();
+            lines
+        };
+
+        assert_eq!(lines("a b"), [1, 2]);
+        // the rest of the query is still literal
+        assert_eq!(lines("fn main("), [5]);
+    }
+
+    #[test]
+    fn snapshot_leaves_out_files_created_during_the_search() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            fs::write(dir.path().join(format!("{i}.txt")), "hit\n").unwrap();
+        }
+        // the first match means the list has been taken, a file made now mustn't be searched
+        let late = dir.path().join("late.txt");
+        let created = std::sync::Once::new();
+        let options = SearchOptions { snapshot: true, ..options(dir.path(), "hit") }.with_postprocessor(move |result| {
+            if let SearchResult::ContentMatch { .. } = result {
+                created.call_once(|| fs::write(&late, "hit\n").unwrap());
+            }
+            Some(result)
+        });
+
+        let results = search(options);
+
+        let found = content_files(&results);
+        assert_eq!(found.len(), 20);
+        assert!(!found.contains(&"late.txt".to_string()));
+        assert!(dir.path().join("late.txt").exists());
+    }
+
+    #[test]
+    fn match_scope_splits_comments_from_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = "// todo: parse it\nfn todo() {\n    let url = \"http://todo\";\n    /* todo */ todo!()\n}\n";
+        fs::write(dir.path().join("lib.rs"), source).unwrap();
+        let spans = |scope| {
+            let mut spans: Vec<_> = search(SearchOptions { scope, ..options(dir.path(), "todo") }).into_iter().filter_map(|r| match r {
+                SearchResult::ContentMatch { line_number, match_start_col, .. } => Some((line_number, match_start_col)),
+                _ => None,
+            }).collect();
+            spans.sort();
+            spans
+        };
+
+        assert_eq!(spans(MatchScope::CommentsOnly), [(1, 3), (4, 7)]);
+        // a // inside a string isn't a comment
+        assert_eq!(spans(MatchScope::CodeOnly), [(2, 3), (3, 22), (4, 15)]);
+        assert_eq!(spans(MatchScope::Anywhere).len(), 5);
+    }
+
+    #[test]
+    fn raw_lines_rebuild_the_file_region() {
+        let dir = tempfile::tempdir().unwrap();
+        let bytes = b"head\r\nhit one\r\nhit two\nhit \xff three";
+        fs::write(dir.path().join("a.txt"), bytes).unwrap();
+
+        let mut lines: Vec<_> = search(SearchOptions { include_raw_line: true, ..options(dir.path(), "hit") }).into_iter().filter_map(|r| match r {
+            SearchResult::ContentMatch { line_number, raw_line, .. } => Some((line_number, raw_line)),
+            _ => None,
+        }).collect();
+        lines.sort();
+
+        // terminators and the invalid UTF-8 byte come through as they are
+        let rebuilt: Vec<u8> = lines.into_iter().flat_map(|(_, raw)| raw).collect();
+        assert_eq!(rebuilt, &bytes[6..]);
+    }
+
+    #[test]
+    fn postprocessor_redacts_and_drops() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("secret.txt"), "password=hunter2\n").unwrap();
+        let options = SearchOptions {
+            file_query: Some("secret".into()),
+            combine: Combine::Or,
+            ..options(dir.path(), "password")
+        }
+        .with_postprocessor(|mut result| {
+            if let SearchResult::FileNameMatch { .. } = result {
+                return None;
+            }
+            if let SearchResult::ContentMatch { ref mut line_text, .. } = result {
+                *line_text = line_text.replace("hunter2", "***");
+            }
+            Some(result)
+        });
+
+        let results = search(options);
+
+        assert!(file_names(&results).is_empty());
+        let lines: Vec<_> = results.iter().filter_map(|r| match r {
+            SearchResult::ContentMatch { line_text, .. } => Some(line_text.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(lines, ["password=***"]);
+    }
+
+    #[test]
+    fn line_range_includes_both_ends() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "hit\n".repeat(8)).unwrap();
+
+        let mut lines = content_lines(&search(SearchOptions { line_range: Some((3, 5)), ..options(dir.path(), "hit") }));
+        lines.sort();
+
+        assert_eq!(lines, [3, 4, 5]);
+    }
+
+    #[test]
+    fn adaptive_depth_only_caps_slow_roots() {
+        let adaptive = SearchOptions { adaptive_depth: true, ..SearchOptions::default() };
+        assert_eq!(adaptive_max_depth(&adaptive, true), Some(ADAPTIVE_MAX_DEPTH));
+        assert_eq!(adaptive_max_depth(&adaptive, false), None);
+        // a depth that was asked for wins
+        assert_eq!(adaptive_max_depth(&SearchOptions { max_depth: Some(10), ..adaptive }, true), None);
+        assert_eq!(adaptive_max_depth(&SearchOptions::default(), true), None);
+    }
+
+    #[test]
+    fn adaptive_depth_leaves_a_local_root_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let deep = dir.path().join("a/b/c/d/e/f");
+        fs::create_dir_all(&deep).unwrap();
+        fs::write(deep.join("deep.txt"), "hit\n").unwrap();
+
+        let results = search(SearchOptions { adaptive_depth: true, ..options(dir.path(), "hit") });
+
+        assert_eq!(content_files(&results), ["deep.txt"]);
+        assert!(!results.iter().any(|r| matches!(r, SearchResult::DepthCapped { .. })));
+    }
+
+    #[test]
+    fn phonetic_file_names() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["steven_notes.txt", "Stefan.doc", "robert.txt", "stone.txt"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let results = search(SearchOptions {
+            root: dir.path().display().to_string(),
+            file_query: Some("Stephen".into()),
+            phonetic: true,
+            ..SearchOptions::default()
+        });
+
+        assert_eq!(file_names(&results), ["Stefan.doc", "steven_notes.txt"]);
+        let note = results.iter().find_map(|r| match r {
+            SearchResult::FileNameMatch { path, note } if path.ends_with("steven_notes.txt") => note.clone(),
+            _ => None,
+        });
+        assert_eq!(note.as_deref(), Some("steven ~ Stephen (S315)"));
+    }
+
+    #[test]
+    fn numbering_counts_lines_and_columns_from_one_or_zero() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "none\nsay hit\n").unwrap();
+
+        let spans = |numbering| {
+            search(SearchOptions { numbering, ..options(dir.path(), "hit") }).into_iter().filter_map(|r| match r {
+                SearchResult::ContentMatch { line_number, column, match_start_col, .. } => Some((line_number, column, match_start_col)),
+                _ => None,
+            }).collect::<Vec<_>>()
+        };
+
+        // match_start_col slices line_text either way
+        assert_eq!(spans(Numbering::default()), [(2, 5, 4)]);
+        assert_eq!(spans(Numbering::ZeroBased), [(1, 4, 4)]);
+    }
+
+    #[test]
+    fn differs_from_reports_changed_copies_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let reference = dir.path().join("app.conf");
+        fs::write(&reference, "port = 80\n").unwrap();
+        for (folder, contents) in [("same", "port = 80\n"), ("edited", "port = 81\n"), ("longer", "port = 80\nhost = a\n")] {
+            fs::create_dir(dir.path().join(folder)).unwrap();
+            fs::write(dir.path().join(folder).join("app.conf"), contents).unwrap();
+        }
+        fs::write(dir.path().join("same/other.conf"), "port = 81\n").unwrap();
+
+        let results = search(SearchOptions {
+            root: dir.path().display().to_string(),
+            differs_from: Some(reference.clone()),
+            ..SearchOptions::default()
+        });
+
+        let mut folders: Vec<_> = results.iter().filter_map(|r| match r {
+            SearchResult::FileNameMatch { path, note } => {
+                assert_eq!(note.as_deref(), Some(format!("differs from {}", reference.display()).as_str()));
+                Some(path.parent()?.file_name()?.to_string_lossy().into_owned())
+            }
+            _ => None,
+        }).collect();
+        folders.sort();
+        assert_eq!(folders, ["edited", "longer"]);
+    }
+
+    #[test]
+    fn ascii_only_refuses_non_ascii_queries() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "café\ncafe\n").unwrap();
+
+        let run = |options: SearchOptions| run_search(options, mpsc::channel().0, Arc::new(AtomicBool::new(false)));
+        assert!(matches!(run(SearchOptions { ascii_only: true, ..options(dir.path(), "café") }), Err(SearchError::NonAsciiQuery)));
+        let by_name = SearchOptions { ascii_only: true, file_query: Some("é".into()), ..options(dir.path(), "cafe") };
+        assert!(matches!(run(by_name), Err(SearchError::NonAsciiQuery)));
+
+        assert_eq!(content_lines(&search(SearchOptions { ascii_only: true, ..options(dir.path(), "cafe") })), [2]);
+    }
+
+    #[test]
+    fn ignore_case_folds_unicode() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "STRASSE\nStraße\nSTRAẞE\nstrase\nİSTANBUL\nistanbul\nIstanbul\nILIK\nΣΟΦΟΣ\nσοφοσ\n").unwrap();
+
+        let lines = |query: &str| content_lines(&search(SearchOptions { ignore_case: true, ..options(dir.path(), query) }));
+        assert_eq!(lines("straße"), [1, 2, 3]);
+        // İ lowercases to a plain i, the dotless I doesn't
+        assert_eq!(lines("İstanbul"), [5, 6]);
+        assert_eq!(lines("ılık"), [8]);
+        // final ς folds like σ and Σ
+        assert_eq!(lines("σοφος"), [9, 10]);
+    }
+
+    #[test]
+    fn file_ignore_case_folds_unicode() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("files");
+        fs::create_dir(&sub).unwrap();
+        for name in ["STRASSE.txt", "Straße.txt", "plan.txt"] {
+            fs::write(sub.join(name), "").unwrap();
+        }
+
+        let results = search(SearchOptions {
+            root: sub.display().to_string(),
+            file_query: Some("straße".into()),
+            file_ignore_case: true,
+            ..SearchOptions::default()
+        });
+        assert_eq!(file_names(&results), ["STRASSE.txt", "Straße.txt"]);
+    }
+
+    #[test]
+    fn utf16_and_late_binary_are_not_text() {
+        let utf16: Vec<u8> = "plain words in utf-16\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert!(!looks_like_text(&utf16));
+
+        // a clean first KiB, then control bytes without a single NUL
+        let mut blob = b"hit ".repeat(256);
+        blob.extend((0..4096u32).map(|i| (i % 8 + 1) as u8));
+        assert!(memchr(0, &blob).is_none());
+        assert!(!looks_like_text(&blob));
+
+        // bigger than the probe with only the middle binary
+        let mut big = b"text line\n".repeat(2000);
+        let middle = big.len() / 2;
+        big[middle - 1000..middle + 1000].fill(0x01);
+        assert!(!looks_like_text(&big));
+
+        assert!(looks_like_text("tabs\tand\r\nnewlines, ümlauts too\n".as_bytes()));
+        assert!(looks_like_text(b""));
+    }
+
+    #[test]
+    fn binary_files_get_no_content_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut blob = b"hit ".repeat(256);
+        blob.extend((0..4096u32).map(|i| (i % 8 + 1) as u8));
+        fs::write(dir.path().join("blob.bin"), blob).unwrap();
+        fs::write(dir.path().join("a.txt"), "hit\n").unwrap();
+
+        assert_eq!(content_files(&search(options(dir.path(), "hit"))), ["a.txt"]);
+    }
+
+    #[test]
+    fn max_file_size_skips_contents_but_not_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("files");
+        fs::create_dir(&sub).unwrap();
+        fs::write(sub.join("huge_hit.txt"), "hit\n".repeat(100)).unwrap();
+        fs::write(sub.join("small.txt"), "hit\n").unwrap();
+
+        let results = search(SearchOptions {
+            root: sub.display().to_string(),
+            text_query: Some("hit".into()),
+            file_query: Some("huge".into()),
+            combine: Combine::Or,
+            max_file_size: Some(100),
+            ..SearchOptions::default()
+        });
+
+        assert_eq!(content_files(&results), ["small.txt"]);
+        assert_eq!(file_names(&results), ["huge_hit.txt"]);
+    }
+
+    // (type, path, line_number, line_text) of every row in a CSV export, with the quoting undone
+    fn csv_rows(csv: &str) -> Vec<Vec<String>> {
+        let (mut rows, mut row, mut field) = (Vec::new(), Vec::new(), String::new());
+        let (mut quoted, mut chars) = (false, csv.chars().peekable());
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => row.push(std::mem::take(&mut field)),
+                '\r' if !quoted => {}
+                '\n' if !quoted => {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                c => field.push(c),
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn exports_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("files");
+        fs::create_dir(&sub).unwrap();
+        fs::write(sub.join("a.txt"), "plain hit\nhit, \"quoted\"\n").unwrap();
+        fs::write(sub.join("hit.log"), "").unwrap();
+
+        let mut results = search(SearchOptions {
+            root: sub.display().to_string(),
+            text_query: Some("hit".into()),
+            file_query: Some("hit".into()),
+            combine: Combine::Or,
+            ..SearchOptions::default()
+        });
+        results.retain(|r| matches!(r, SearchResult::ContentMatch { .. } | SearchResult::FileNameMatch { .. }));
+        results.sort_by_key(|r| format!("{:?}", r));
+        // a line break in the text has to survive the CSV quoting too
+        if let Some(SearchResult::ContentMatch { line_text, .. }) = results.iter_mut().find(|r| matches!(r, SearchResult::ContentMatch { .. })) {
+            line_text.push_str("\nand more");
+        }
+        results.push(SearchResult::ProgressUpdate(7));
+
+        let expected: Vec<Vec<String>> = results.iter().filter_map(|r| match r {
+            SearchResult::ContentMatch { path, line_number, line_text, .. } => {
+                Some(vec!["content_match".into(), path.display().to_string(), line_number.to_string(), line_text.clone()])
+            }
+            SearchResult::FileNameMatch { path, .. } => Some(vec!["file_name_match".into(), path.display().to_string(), String::new(), String::new()]),
+            _ => None,
+        }).collect();
+        assert_eq!(expected.len(), 3);
+
+        let mut csv = Vec::new();
+        export_results(&results, ExportFormat::Csv, &mut csv).unwrap();
+        let rows = csv_rows(&String::from_utf8(csv).unwrap());
+        assert_eq!(rows[0], ["type", "path", "line_number", "line_text"]);
+        assert_eq!(rows[1..], expected[..]);
+
+        #[cfg(feature = "json")]
+        {
+            let mut json = Vec::new();
+            export_results(&results, ExportFormat::Json, &mut json).unwrap();
+            let rows: Vec<serde_json::Value> = serde_json::from_slice(&json).unwrap();
+            let rows: Vec<Vec<String>> = rows.iter().map(|row| {
+                ["type", "path", "line_number", "line_text"].iter().map(|key| match &row[key] {
+                    serde_json::Value::String(s) => s.clone(),
+                    serde_json::Value::Null => String::new(),
+                    other => other.to_string(),
+                }).collect()
+            }).collect();
+            assert_eq!(rows, expected);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follow_symlinks_survives_a_loop() {
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().join("outside");
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir(&real).unwrap();
+        fs::write(real.join("a.txt"), "hit\n").unwrap();
+        std::os::unix::fs::symlink(&real, root.join("linked")).unwrap();
+        // points back at its own parent
+        std::os::unix::fs::symlink(&root, root.join("sub/loop")).unwrap();
+
+        let root_options = |follow_symlinks| SearchOptions { follow_symlinks, ..options(&root, "hit") };
+        assert_eq!(content_files(&search(root_options(true))), ["a.txt"]);
+        assert!(content_files(&search(root_options(false))).is_empty());
+    }
+
+    #[test]
+    fn exclude_dirs_skips_listed_folders_only() {
+        let dir = tempfile::tempdir().unwrap();
+        for folder in ["node_modules", "src", ".Trash-1000"] {
+            fs::create_dir(dir.path().join(folder)).unwrap();
+            fs::write(dir.path().join(folder).join(format!("{}.txt", folder.trim_start_matches('.'))), "hit\n").unwrap();
+        }
+
+        assert_eq!(content_files(&search(options(dir.path(), "hit"))), ["src.txt"]);
+        let exclude_dirs = vec![".Trash*".to_string()];
+        let edited = search(SearchOptions { exclude_dirs, ..options(dir.path(), "hit") });
+        assert_eq!(content_files(&edited), ["node_modules.txt", "src.txt"]);
+    }
+
+    #[test]
+    fn empty_files_still_match_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("files");
+        fs::create_dir(&sub).unwrap();
+        fs::write(sub.join("empty_notes.txt"), "").unwrap();
+        fs::write(sub.join("a.txt"), "notes\n").unwrap();
+
+        assert!(load_file(&sub.join("empty_notes.txt"), None).unwrap().bytes().is_empty());
+        let results = search(SearchOptions {
+            root: sub.display().to_string(),
+            text_query: Some("notes".into()),
+            file_query: Some("notes".into()),
+            combine: Combine::Or,
+            ..SearchOptions::default()
+        });
+        assert_eq!(file_names(&results), ["empty_notes.txt"]);
+        assert_eq!(content_files(&results), ["a.txt"]);
+    }
+
+    #[test]
+    fn read_file_keeps_to_max_len() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "twelve bytes").unwrap();
+
+        assert_eq!(read_file(File::open(&path).unwrap(), Some(12)).unwrap(), b"twelve bytes");
+        assert!(read_file(File::open(&path).unwrap(), Some(11)).unwrap().is_empty());
+    }
+
+    // /proc files say they're empty whatever's in them, so they can't be mapped
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn files_that_say_they_are_empty_get_read() {
+        let status = Path::new("/proc/self/status");
+        assert!(load_file(status, None).unwrap().bytes().starts_with(b"Name:"));
+
+        let results = search_one(status, options(Path::new("/proc/self"), "Name:"), false);
+        assert_eq!(content_lines(&results), [1]);
+    }
+
+    #[test]
+    fn whole_word_needs_a_boundary_on_both_sides() {
+        let dir = tempfile::tempdir().unwrap();
+        // at the start of the file, mid-line, after punctuation, inside words, at the end of the file
+        fs::write(dir.path().join("a.txt"), "int x;\npoint printf integer\n(int)\nsize_int int2\nx = int").unwrap();
+
+        let whole = |query: &str| content_lines(&search(SearchOptions { whole_word: true, ..options(dir.path(), query) }));
+        assert_eq!(whole("int"), [1, 3, 5]);
+        assert_eq!(content_lines(&search(options(dir.path(), "int"))).len(), 8);
+
+        assert!(is_whole_word(b"int", 0, 3));
+        assert!(is_whole_word(b"a\nint\n", 2, 5));
+        assert!(!is_whole_word("äint".as_bytes(), 2, 5));
+    }
+
+    #[test]
+    fn regex_anchors_match_every_line_and_matches_stay_on_theirs() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.rs"), "héllo wörld\nfn main() {}\r\n  fn inner() {}\nabc\n\nxyz\n").unwrap();
+
+        let lines = |query: &str| content_lines(&search(SearchOptions { regex: true, ..options(dir.path(), query) }));
+        assert_eq!(lines("^fn"), [2]);
+        assert_eq!(lines(r"^\s*fn"), [2, 3]);
+        assert_eq!(lines("^abc$"), [4]);
+        assert_eq!(lines("xyz$"), [6]);
+        assert_eq!(lines(r"\{\}$"), [2, 3]);
+        // the line break isn't whitespace a match can take
+        assert!(lines(r"c\s+x").is_empty());
+        // but a shorter match on the same line still counts
+        let spans: Vec<_> = search(SearchOptions { regex: true, ..options(dir.path(), r"abc\s*") }).into_iter().filter_map(|r| match r {
+            SearchResult::ContentMatch { line_number, match_start_col, match_end_col, .. } => Some((line_number, match_start_col, match_end_col)),
+            _ => None,
+        }).collect();
+        assert_eq!(spans, [(4, 0, 3)]);
+    }
+
+    // count_matches over 20k two-line files, the tree of many small files small-file-reads is for.
+    // Compare a run with the feature and one without:
+    //     cargo test --release --lib read_path_benchmark -- --ignored --nocapture
+    //     cargo test --release --lib --features small-file-reads read_path_benchmark -- --ignored --nocapture
+    #[test]
+    #[ignore = "benchmark, see the comment above it"]
+    fn read_path_benchmark() {
+        let dir = tempfile::tempdir().unwrap();
+        for folder in 0..20 {
+            let folder = dir.path().join(folder.to_string());
+            fs::create_dir(&folder).unwrap();
+            for file in 0..1000 {
+                fs::write(folder.join(format!("{file}.txt")), "some text\nwith a hit in it\n").unwrap();
+            }
+        }
+
+        let run = || count_matches(options(dir.path(), "hit")).unwrap();
+        // the first walk warms the page cache
+        assert_eq!(run().total_matches, 20_000);
+        let mut times: Vec<_> = (0..5).map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        }).collect();
+        times.sort();
+        let backend = if cfg!(all(feature = "small-file-reads", target_os = "linux")) { "small-file-reads" } else { "mmap" };
+        println!("{}: median {:?}, fastest {:?} of 5 runs", backend, times[2], times[0]);
+    }
+}
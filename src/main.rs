@@ -3,9 +3,11 @@ use std::sync::mpsc::{self, Receiver};
 use std::thread;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
 
 
-use fast_search::{run_search, SearchOptions, SearchResult}; 
+use fast_search::{export_results, load_results_json, run_search, search_file, ErrorReporting, Combine, ErrorSource, ExportFormat, Extractors, MatchMode, MatchScope, Normalization, Numbering, SearchError, SearchOptions, SearchResult, ADAPTIVE_MAX_DEPTH, DEFAULT_EXCLUDE_DIRS, DEFAULT_SECTION_MARKER};
 
 fn main() -> eframe::Result<(), eframe::Error> {
     let native_options = eframe::NativeOptions{
@@ -16,7 +18,7 @@ fn main() -> eframe::Result<(), eframe::Error> {
     eframe::run_native(
         "Fast Search Engine",
         native_options,
-        Box::new(|_cc| Ok(Box::new(FastSearchApp::default()))),
+        Box::new(|cc| Ok(Box::new(FastSearchApp::new(cc)))),
     )
 }
 
@@ -25,14 +27,132 @@ struct FastSearchApp {
     root_path: String,
     search_term: String,
     file_name: String,
-    ignore_case: bool,
-    max_depth: usize,
+    text_ignore_case: bool,
+    file_ignore_case: bool,
+    whole_line: bool,
+    whole_word: bool,
+    line_prefix: bool,
+    line_prefix_trim: bool,
+    // regex for the lines that start a section, None doesn't look for sections
+    section_marker: Option<String>,
+    text_only: bool,
+    search_gzip: bool,
+    max_matches_per_file: Option<usize>,
+    max_line_len: Option<usize>,
+    respect_gitignore: bool,
+    respect_parent_ignores: bool,
+    respect_global_gitignore: bool,
+    git_tracked_only: bool,
+    git_changed_only: bool,
+    only_gitignored: bool,
+    search_all: bool,
+    // comma separated folder names the walk skips
+    exclude_dirs: String,
+    dedup_hardlinks: bool,
+    follow_symlinks: bool,
+    hidden_only: bool,
+    alternate_streams: bool,
+    // seconds before the search gives up, None runs until it's done
+    time_limit: Option<u64>,
+    // in MB, files over it only get their names matched
+    max_file_mb: Option<u64>,
+    // stops the search after this many results
+    max_results: Option<usize>,
+    skip_first_lines: usize,
+    // lines shown dimmed above and below each match
+    context: usize,
+    // first and last line to keep matches from, None keeps all of them
+    line_range: Option<(usize, usize)>,
+    only_matching: bool,
+    normalize_unicode: bool,
+    unicode_case: bool,
+    flexible_whitespace: bool,
+    // every word of the text box is its own term (text_query plus more_text_queries)
+    separate_terms: bool,
+    // with separate_terms, a file needs every one of them (MatchMode::All)
+    all_terms: bool,
+    // the text query is a regex
+    regex: bool,
+    scope: MatchScope,
+    detect_encoding: bool,
+    local_only: bool,
+    adaptive_depth: bool,
+    // report_timing with SLOW_FILE
+    show_slow_files: bool,
+    // who last changed each matched line, from git blame
+    blame: bool,
+    // opens the result straight away when a search finds exactly one
+    open_single: bool,
+    // one box for both the text and the file name query
+    search_everywhere: bool,
+    timed_out: bool,
+    // the search stopped at max_results, this many
+    stopped_at: Option<usize>,
+    max_depth: Option<usize>,
+    // last depth picked, brought back when the limit gets switched on again
+    depth_limit: usize,
     file_types: Option<String>,
+    file_regex: bool,
+    // file names match a glob like *.rs
+    file_glob: bool,
+    // file names match by how they sound
+    phonetic: bool,
+    match_full_path: bool,
+    // why the file name regex didn't compile
+    pattern_error: Option<String>,
+    error_reporting: ErrorReporting,
+    error_summary: Option<(usize, usize)>,
     file_scanned: usize,
+    // what the search counted up front, the status bar shows a spinner without it
+    total_entries: Option<usize>,
     has_searched: bool,
-    results: Vec<SearchResult>,
+    // what goes in the results list, see ResultStore
+    results: ResultStore,
+    // rows whose long line has been expanded past the preview
+    expanded_rows: HashSet<usize>,
+    preview_chars: usize,
+    // options_summary of the search the results came from
+    applied_options: Vec<String>,
+    // root_path as the running search got it, editing the box away from it cancels the search
+    searched_root: String,
+    // the results came from a JSON Lines file instead of a search, with how many of its lines were skipped
+    imported: Option<(std::path::PathBuf, usize)>,
+    // which PAGE_SIZE slice of results is shown. Only the rows of it being drawn get built back into
+    // a SearchResult, the whole set stays in results' compact rows
+    page: usize,
+    // draws runs of spaces/tabs in a line as one space
+    collapse_whitespace: bool,
+    // narrows the results list down to rows containing this, the search itself isn't rerun
+    results_filter: String,
+    // the order the results are listed in, results itself always stays in the order they came
+    sort_mode: SortMode,
+    // indexes of the results that pass results_filter in sort_mode order, None when there's no
+    // filter and no sorting
+    filtered: Option<Vec<usize>>,
+    // the filter and order filtered was worked out for, and how many results it has looked at
+    filtered_for: (String, usize, SortMode),
+    // Copy as Markdown takes every result instead of just the filtered ones
+    export_all: bool,
+    // the terms the results were searched with, by pattern index
+    applied_terms: Vec<String>,
+    // shows the results under the term that matched instead of in one list
+    group_by_term: bool,
+    // results_filter's rows sorted under their terms, None until it's needed
+    term_groups: Option<TermGroups>,
+    // the filter and order term_groups was worked out for
+    groups_for: (String, SortMode),
+    // one collapsible entry per file instead of a row per matching line
+    group_by_file: bool,
+    // the results sorted under their files so far, None until it's needed
+    file_groups: Option<FileGroups>,
+    // the filter and order file_groups was worked out for
+    file_groups_for: (String, SortMode),
+    // every folder in a result's path is its own clickable link
+    breadcrumbs: bool,
     is_searching: bool,
     cancel_token: Arc<AtomicBool>,
+    settings: Settings,
+    settings_open: bool,
     
    
     receiver: Option<Receiver<SearchResult>>,
@@ -50,15 +170,91 @@ impl Default for FastSearchApp {
                 .unwrap_or_else(|_| ".".to_string()),
             search_term: "".to_string(),
             file_name: "".to_string(),
-            ignore_case: false,
-            max_depth: 255,
+            text_ignore_case: false,
+            file_ignore_case: false,
+            whole_line: false,
+            whole_word: false,
+            line_prefix: false,
+            line_prefix_trim: false,
+            section_marker: None,
+            text_only: false,
+            search_gzip: false,
+            max_matches_per_file: None,
+            max_line_len: None,
+            respect_gitignore: true,
+            respect_parent_ignores: true,
+            respect_global_gitignore: true,
+            git_tracked_only: false,
+            git_changed_only: false,
+            only_gitignored: false,
+            search_all: false,
+            exclude_dirs: DEFAULT_EXCLUDE_DIRS.join(", "),
+            dedup_hardlinks: false,
+            follow_symlinks: false,
+            hidden_only: false,
+            alternate_streams: false,
+            time_limit: None,
+            max_file_mb: None,
+            max_results: None,
+            skip_first_lines: 0,
+            context: 0,
+            line_range: None,
+            only_matching: false,
+            normalize_unicode: false,
+            unicode_case: false,
+            flexible_whitespace: false,
+            separate_terms: false,
+            all_terms: false,
+            regex: false,
+            scope: MatchScope::Anywhere,
+            detect_encoding: false,
+            local_only: false,
+            adaptive_depth: false,
+            show_slow_files: false,
+            blame: false,
+            open_single: false,
+            search_everywhere: false,
+            timed_out: false,
+            stopped_at: None,
+            max_depth: None,
+            depth_limit: 3,
             file_types: Option::default(),
+            file_regex: false,
+            file_glob: false,
+            phonetic: false,
+            match_full_path: false,
+            pattern_error: None,
+            error_reporting: ErrorReporting::default(),
+            error_summary: None,
             file_scanned: 0,
-            results: Vec::new(),
+            total_entries: None,
+            results: ResultStore::default(),
+            expanded_rows: HashSet::new(),
+            preview_chars: 200,
+            applied_options: Vec::new(),
+            searched_root: String::new(),
+            imported: None,
+            page: 0,
+            collapse_whitespace: false,
+            breadcrumbs: false,
+            results_filter: String::new(),
+            filtered: None,
+            sort_mode: SortMode::Found,
+            filtered_for: (String::new(), 0, SortMode::Found),
+            applied_terms: Vec::new(),
+            group_by_term: false,
+            term_groups: None,
+            groups_for: (String::new(), SortMode::Found),
+            group_by_file: false,
+            file_groups: None,
+            file_groups_for: (String::new(), SortMode::Found),
+            export_all: false,
             is_searching: false,
             has_searched: false,
             receiver: None,
-            cancel_token: cancel_token,
+            cancel_token,
+            settings: Settings::default(),
+            settings_open: false,
             
             
         }
@@ -66,27 +262,61 @@ impl Default for FastSearchApp {
 }
 
 impl eframe::App for FastSearchApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, &self.settings);
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // 1. Setup Visuals
         let mut visuals = egui::Visuals::dark();
         visuals.panel_fill = egui::Color32::from_rgb(30, 30, 30);
         ctx.set_visuals(visuals);
 
+        // a dropped folder becomes the root, a dropped file gets searched on its own
+        if let Some(path) = ctx.input(|i| i.raw.dropped_files.iter().find_map(|f| f.path.clone())) {
+            self.root_path = path.display().to_string();
+        }
+        self.cancel_if_root_changed();
+
         if let Some(ref rx) = self.receiver {
             loop {
                 match rx.try_recv() {
                     Ok(result) => match result {
-                        SearchResult::FileNameMatch { .. } | SearchResult::ContentMatch { .. } => {
+                        SearchResult::FileNameMatch { .. } | SearchResult::ContentMatch { .. } | SearchResult::Error { .. }
+                        | SearchResult::MoreMatches { .. } | SearchResult::MoreEntries { .. }
+                        | SearchResult::RemoteSkipped { .. } | SearchResult::FileTiming { .. }
+                        | SearchResult::DepthCapped { .. } => {
                             self.results.push(result);
                         }
+                        // threads report side by side, an older count can come in after a newer one
                         SearchResult::ProgressUpdate(count) => {
-                            self.file_scanned += count;
+                            self.file_scanned = self.file_scanned.max(count);
+                        }
+                        SearchResult::ProgressTotal(total) => {
+                            self.total_entries = Some(total);
+                        }
+                        SearchResult::ErrorSummary { dir_errors, file_errors } => {
+                            self.error_summary = Some((dir_errors, file_errors));
+                        }
+                        SearchResult::TimedOut => {
+                            self.timed_out = true;
+                        }
+                        SearchResult::ResultLimitReached { results } => {
+                            self.stopped_at = Some(results);
                         }
                     },
                     Err(mpsc::TryRecvError::Empty) => break,
                     Err(mpsc::TryRecvError::Disconnected) => {
                         self.is_searching = false;
                         self.receiver = None;
+                        // same as clicking it, open can't jump to a line so content matches just open the file.
+                        // Not after a cancel or a time limit, the search didn't really finish
+                        if self.open_single
+                            && !self.cancel_token.load(Ordering::Relaxed)
+                            && let Some(path) = single_match(&self.results)
+                        {
+                            let _ = open::that(path);
+                        }
                         break;
                     }
                 }
@@ -96,33 +326,95 @@ impl eframe::App for FastSearchApp {
             }
         }
 
+        egui::Window::new("Settings")
+            .open(&mut self.settings_open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(egui::RichText::new("Result colors").strong());
+                self.settings.theme.edit(ui);
+            });
+
         egui::SidePanel::left("SearchChoices")
             .default_width(280.0)
             .show(ctx, |ui| {
                 ui.add_space(10.0);
-                ui.heading("🔍 FastSearch");
+                ui.horizontal(|ui| {
+                    ui.heading("🔍 FastSearch");
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("⚙").on_hover_text("Settings").clicked() {
+                            self.settings_open = !self.settings_open;
+                        }
+                    });
+                });
                 ui.separator();
 
                 let mut submit_request = false;
                 let input_width = ui.available_width() - 35.0;
 
                 ui.label("Root Path:");
+                self.bookmarks_ui(ui);
                 ui.horizontal(|ui| {
                     ui.add(egui::TextEdit::singleline(&mut self.root_path).desired_width(input_width));
                     
                 });
 
-                if ui.button("📁").clicked() {
-                        if let Some(path) = rfd::FileDialog::new().pick_folder() {
-                            self.root_path = path.display().to_string();
-                        }
+                ui.horizontal(|ui| {
+                    if ui.button("📁").on_hover_text("Search a folder").clicked()
+                        && let Some(path) = self.file_dialog().pick_folder()
+                    {
+                        self.root_path = path.display().to_string();
+                        self.settings.last_dir = Some(path);
+                    }
+                    if ui.button("📄").on_hover_text("Search a single file").clicked()
+                        && let Some(path) = self.file_dialog().pick_file()
+                    {
+                        self.root_path = path.display().to_string();
+                        self.settings.last_dir = path.parent().map(|p| p.to_path_buf());
                     }
+                    let bookmarked = self.settings.bookmarks.contains(&self.root_path);
+                    if !bookmarked
+                        && ui.button("☆").on_hover_text("Bookmark this root").clicked()
+                        && !self.root_path.trim().is_empty()
+                    {
+                        self.settings.bookmarks.push(self.root_path.clone());
+                    }
+                });
 
-                ui.label("Search Text:");
-                let res1 = ui.add(egui::TextEdit::singleline(&mut self.search_term).desired_width(input_width));
-                
-                ui.label("Search File Name:");
-                let res2 = ui.add(egui::TextEdit::singleline(&mut self.file_name).desired_width(input_width));
+                ui.checkbox(&mut self.search_everywhere, "Search everywhere")
+                    .on_hover_text("One box matched against both file names and file contents");
+                let (res1, res2) = if self.search_everywhere {
+                    ui.horizontal(|ui| {
+                        ui.label("Search File Names and Text:");
+                        ui.checkbox(&mut self.regex, "Regex").on_hover_text("The text is searched as a regex, names stay literal");
+                    });
+                    let res = ui.add(egui::TextEdit::singleline(&mut self.search_term).desired_width(input_width));
+                    (res.clone(), res)
+                } else {
+                    ui.horizontal(|ui| {
+                        ui.label("Search Text:");
+                        ui.checkbox(&mut self.regex, "Regex").on_hover_text("e.g. fn \\w+\\( or TODO|FIXME");
+                    });
+                    let res1 = ui.add(egui::TextEdit::singleline(&mut self.search_term).desired_width(input_width));
+
+                    ui.label("Search File Name:");
+                    let res2 = ui.add(egui::TextEdit::singleline(&mut self.file_name).desired_width(input_width));
+                    (res1, res2)
+                };
+                ui.horizontal(|ui| {
+                    if !self.search_everywhere {
+                        ui.add_enabled(!self.phonetic && !self.file_glob, egui::Checkbox::new(&mut self.file_regex, "Regex"))
+                            .on_hover_text("e.g. ^test_.*\\.rs$");
+                        ui.add_enabled(!self.phonetic && !self.file_regex, egui::Checkbox::new(&mut self.file_glob, "Glob"))
+                            .on_hover_text("The whole name has to match, e.g. *.rs or test_??.txt");
+                        ui.checkbox(&mut self.phonetic, "Sounds like")
+                            .on_hover_text("Names with words that sound like the query's, \"Stephen\" finds \"Steven\"");
+                    }
+                    ui.checkbox(&mut self.match_full_path, "Match full path")
+                        .on_hover_text("Matches the path below the root (src/main.rs) instead of just the name");
+                });
+                if let Some(ref err) = self.pattern_error {
+                    ui.label(egui::RichText::new(err).color(egui::Color32::LIGHT_RED));
+                }
 
                 ui.label(egui::RichText::new("File Types/Extensions").color(egui::Color32::WHITE).strong());
 
@@ -147,12 +439,228 @@ impl eframe::App for FastSearchApp {
                 }
 
                 ui.collapsing("Advanced Options", |ui| {
-                    ui.checkbox(&mut self.ignore_case, "Ignore Case");
-                    
-                    let mut depth = self.max_depth as u32;
-                    if ui.add(egui::DragValue::new(&mut depth).range(0..=5000)).changed() {
-                        self.max_depth = depth as usize;
+                    ui.horizontal(|ui| {
+                        ui.label("Ignore case in:");
+                        ui.checkbox(&mut self.text_ignore_case, "Text");
+                        ui.checkbox(&mut self.file_ignore_case, "File names");
+                    });
+                    ui.add_enabled(self.text_ignore_case, egui::Checkbox::new(&mut self.unicode_case, "Unicode case folding"))
+                        .on_hover_text("Folds case the Unicode way for plain ASCII text too (text with Ä in it always does)");
+                    ui.checkbox(&mut self.whole_line, "Match whole line");
+                    ui.checkbox(&mut self.whole_word, "Match whole words")
+                        .on_hover_text("\"int\" finds int and int() but not point or integer");
+                    ui.horizontal(|ui| {
+                        ui.label("Match in:");
+                        ui.selectable_value(&mut self.scope, MatchScope::Anywhere, "Anywhere");
+                        ui.selectable_value(&mut self.scope, MatchScope::CommentsOnly, "Comments");
+                        ui.selectable_value(&mut self.scope, MatchScope::CodeOnly, "Code");
+                    });
+                    ui.add_enabled(!self.regex, egui::Checkbox::new(&mut self.flexible_whitespace, "Flexible whitespace"))
+                        .on_hover_text("A space in the text matches any amount of spaces or tabs");
+                    ui.checkbox(&mut self.separate_terms, "Each word is its own term")
+                        .on_hover_text("Lines with any of the words match, each word gets its own color");
+                    ui.add_enabled(self.separate_terms, egui::Checkbox::new(&mut self.all_terms, "Files need every word"))
+                        .on_hover_text("Only files where each of the words matches somewhere are shown");
+                    ui.checkbox(&mut self.only_matching, "Show only the match")
+                        .on_hover_text("Like grep -o, each match gets its own row with just the matched text");
+                    ui.checkbox(&mut self.detect_encoding, "Decode UTF-16 files")
+                        .on_hover_text("Files starting with a byte order mark are decoded first, the encoding is shown next to the path");
+                    ui.checkbox(&mut self.normalize_unicode, "Normalize unicode")
+                        .on_hover_text("Lets \"café\" match however the accent is stored, slower on non-ASCII files");
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.line_prefix, "Match at line start");
+                        ui.add_enabled(self.line_prefix, egui::Checkbox::new(&mut self.line_prefix_trim, "after indentation"));
+                    });
+                    let mut sections = self.section_marker.is_some();
+                    if ui.checkbox(&mut sections, "Show enclosing section")
+                        .on_hover_text("Shows the nearest function/class/block line above each match")
+                        .changed()
+                    {
+                        self.section_marker = sections.then(|| DEFAULT_SECTION_MARKER.to_string());
+                    }
+                    if let Some(ref mut marker) = self.section_marker {
+                        ui.horizontal(|ui| {
+                            ui.label("Section regex:");
+                            ui.text_edit_singleline(marker);
+                        });
+                    }
+                    ui.checkbox(&mut self.text_only, "Text files only")
+                        .on_hover_text("Skips anything that looks binary, even without an extension");
+                    ui.checkbox(&mut self.search_gzip, "Search inside .gz files");
+                    if cfg!(windows) {
+                        ui.checkbox(&mut self.alternate_streams, "Search alternate data streams")
+                            .on_hover_text("Also searches NTFS streams like file.txt:stream");
                     }
+                    
+                    ui.horizontal(|ui| {
+                        let mut limited = self.max_depth.is_some();
+                        if ui.checkbox(&mut limited, "Max depth").changed() {
+                            self.max_depth = limited.then_some(self.depth_limit);
+                        }
+                        if ui
+                            .add_enabled(limited, egui::DragValue::new(&mut self.depth_limit).range(0..=5000))
+                            .changed()
+                        {
+                            self.max_depth = Some(self.depth_limit);
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        for preset in [1, 3] {
+                            if ui.selectable_label(self.max_depth == Some(preset), preset.to_string()).clicked() {
+                                self.depth_limit = preset;
+                                self.max_depth = Some(preset);
+                            }
+                        }
+                        if ui.selectable_label(self.max_depth.is_none(), "Unlimited").clicked() {
+                            self.max_depth = None;
+                        }
+                    });
+
+                    ui.label("Respect ignore files:");
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.respect_gitignore, "Repo")
+                            .on_hover_text("The repo's .gitignore files, unchecking this turns off all of them");
+                        ui.checkbox(&mut self.respect_parent_ignores, "Parents")
+                            .on_hover_text("Ignore files in folders above the root");
+                        ui.checkbox(&mut self.respect_global_gitignore, "Global")
+                            .on_hover_text("Your global gitignore (core.excludesFile)");
+                    });
+
+                    ui.checkbox(&mut self.hidden_only, "Only hidden files")
+                        .on_hover_text("Dotfiles like .env and .npmrc, and everything in hidden folders");
+                    ui.checkbox(&mut self.local_only, "Skip network and removable drives");
+                    ui.add_enabled(self.max_depth.is_none(), egui::Checkbox::new(&mut self.adaptive_depth, "Limit depth on network drives"))
+                        .on_hover_text(format!("A root on a network share or removable drive is only searched {} folders deep", ADAPTIVE_MAX_DEPTH));
+                    ui.checkbox(&mut self.dedup_hardlinks, "Search hardlinked files once")
+                        .on_hover_text("Matches show up under the first path found for the file");
+                    ui.checkbox(&mut self.follow_symlinks, "Follow symlinks")
+                        .on_hover_text("Searches inside symlinked folders, a link that loops back is skipped");
+                    ui.checkbox(&mut self.search_all, "Search system folders too")
+                        .on_hover_text("Also looks inside the folders below and /proc, /sys and /dev");
+                    ui.horizontal(|ui| {
+                        ui.label("Exclude folders:");
+                        ui.add_enabled(!self.search_all, egui::TextEdit::singleline(&mut self.exclude_dirs).desired_width(220.0))
+                            .on_hover_text("Folder names skipped wherever they are, comma separated. .Trash* skips every name starting with .Trash");
+                        if ui.small_button("↺").on_hover_text("Back to the defaults").clicked() {
+                            self.exclude_dirs = DEFAULT_EXCLUDE_DIRS.join(", ");
+                        }
+                    });
+                    ui.checkbox(&mut self.git_tracked_only, "Only files tracked by git")
+                        .on_hover_text("Like git grep, falls back to a normal search outside a repo");
+                    ui.checkbox(&mut self.git_changed_only, "Only files with uncommitted changes")
+                        .on_hover_text("Modified, added and untracked files, falls back to a normal search outside a repo");
+                    ui.checkbox(&mut self.only_gitignored, "Only files git ignores")
+                        .on_hover_text("Build output, caches and anything else .gitignore leaves out");
+
+                    ui.horizontal(|ui| {
+                        let mut capped = self.max_matches_per_file.is_some();
+                        if ui.checkbox(&mut capped, "Max matches per file").changed() {
+                            self.max_matches_per_file = capped.then_some(100);
+                        }
+                        if let Some(ref mut cap) = self.max_matches_per_file {
+                            ui.add(egui::DragValue::new(cap).range(1..=100_000));
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        let mut capped = self.max_line_len.is_some();
+                        if ui.checkbox(&mut capped, "Max line length").changed() {
+                            self.max_line_len = capped.then_some(1000);
+                        }
+                        if let Some(ref mut max) = self.max_line_len {
+                            ui.add(egui::DragValue::new(max).range(10..=1_000_000).suffix(" bytes"));
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Skip first lines:");
+                        ui.add(egui::DragValue::new(&mut self.skip_first_lines).range(0..=1_000_000))
+                            .on_hover_text("Leaves matches in each file's header out, line numbers still count from the top");
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Context lines:");
+                        ui.add(egui::DragValue::new(&mut self.context).range(0..=20))
+                            .on_hover_text("Shows this many lines above and below each match, like grep -C");
+                    });
+
+                    ui.horizontal(|ui| {
+                        let mut ranged = self.line_range.is_some();
+                        if ui.checkbox(&mut ranged, "Only lines").changed() {
+                            self.line_range = ranged.then_some((1, 100));
+                        }
+                        if let Some((ref mut first, ref mut last)) = self.line_range {
+                            ui.add(egui::DragValue::new(first).range(1..=*last));
+                            ui.label("to");
+                            ui.add(egui::DragValue::new(last).range(*first..=usize::MAX));
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        let mut limited = self.time_limit.is_some();
+                        if ui.checkbox(&mut limited, "Time limit").changed() {
+                            self.time_limit = limited.then_some(30);
+                        }
+                        if let Some(ref mut secs) = self.time_limit {
+                            ui.add(egui::DragValue::new(secs).range(1..=86_400).suffix(" s"));
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        let mut limited = self.max_file_mb.is_some();
+                        if ui.checkbox(&mut limited, "Max file size")
+                            .on_hover_text("Bigger files aren't searched inside, their names still match")
+                            .changed()
+                        {
+                            self.max_file_mb = limited.then_some(100);
+                        }
+                        if let Some(ref mut mb) = self.max_file_mb {
+                            ui.add(egui::DragValue::new(mb).range(1..=1_000_000).suffix(" MB"));
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        let mut limited = self.max_results.is_some();
+                        if ui.checkbox(&mut limited, "Max results")
+                            .on_hover_text("Stops the search once it has found this many")
+                            .changed()
+                        {
+                            self.max_results = limited.then_some(100);
+                        }
+                        if let Some(ref mut max) = self.max_results {
+                            ui.add(egui::DragValue::new(max).range(1..=1_000_000));
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Line preview length:");
+                        ui.add(egui::DragValue::new(&mut self.preview_chars).range(20..=5000));
+                    });
+                    ui.checkbox(&mut self.open_single, "Open the result if it's the only one");
+                    ui.checkbox(&mut self.show_slow_files, "Show slow files")
+                        .on_hover_text("Lists every file that took over 100 ms to read and scan");
+                    ui.checkbox(&mut self.blame, "Show git blame")
+                        .on_hover_text("Who last changed each matched line, slower since git runs for every file with a match");
+                    ui.checkbox(&mut self.breadcrumbs, "Clickable folders in paths")
+                        .on_hover_text("Click any folder in a result's path to open it");
+                    ui.checkbox(&mut self.collapse_whitespace, "Collapse whitespace in lines")
+                        .on_hover_text("Only changes how lines are shown, copying keeps the real text");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Unreadable entries:");
+                        egui::ComboBox::from_id_salt("error_reporting")
+                            .selected_text(match self.error_reporting {
+                                ErrorReporting::Silent => "Ignore",
+                                ErrorReporting::Summary => "Count",
+                                ErrorReporting::PerEntry => "List each",
+                            })
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.error_reporting, ErrorReporting::Silent, "Ignore");
+                                ui.selectable_value(&mut self.error_reporting, ErrorReporting::Summary, "Count");
+                                ui.selectable_value(&mut self.error_reporting, ErrorReporting::PerEntry, "List each");
+                            });
+                    });
                 });
 
                 ui.add_space(20.0);
@@ -162,13 +670,17 @@ impl eframe::App for FastSearchApp {
                         let cancel_btn = egui::Button::new(egui::RichText::new("🛑 Cancel").color(egui::Color32::WHITE))
                             .fill(egui::Color32::from_rgb(200, 40, 40));
                         if ui.add(cancel_btn).clicked() {
-                            self.cancel_token.store(true, std::sync::atomic::Ordering::Relaxed);
-                            self.is_searching = false;
+                            self.cancel_search();
                         }
                     } else {
                         if ui.button("🚀 Start Search").clicked() || submit_request {
                             self.execute_search(ctx.clone());
                         }
+                        if ui.button("📂 Import results").on_hover_text("Loads a .jsonl file a search wrote earlier").clicked()
+                            && let Some(path) = self.file_dialog().add_filter("JSON Lines", &["jsonl", "json"]).pick_file()
+                        {
+                            self.import_results(path);
+                        }
                     }
                 }); 
             });
@@ -176,13 +688,83 @@ impl eframe::App for FastSearchApp {
         if self.is_searching {
             egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
                 ui.horizontal(|ui| {
-                    ui.add(egui::Spinner::new().size(12.0));
-                    ui.label(format!("Scanning... ({} files)", self.file_scanned));
+                    match self.total_entries {
+                        Some(total) if total > 0 => {
+                            let done = (self.file_scanned as f32 / total as f32).min(1.0);
+                            ui.add(egui::ProgressBar::new(done).desired_width(160.0).show_percentage());
+                            // the total counts folders as well as files, so the count does too
+                            ui.label(format!("Scanning... ({} of {} entries)", self.file_scanned.min(total), total));
+                        }
+                        _ => {
+                            ui.add(egui::Spinner::new().size(12.0));
+                            ui.label(format!("Scanning... ({} files)", self.file_scanned));
+                        }
+                    }
+                    // the side panel's cancel can be scrolled out of view, so there's one down here too
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("🛑 Cancel").clicked() {
+                            self.cancel_search();
+                        }
+                    });
                 });
             });
         }
 
+        if let Some(results) = self.stopped_at
+            && !self.is_searching
+        {
+            egui::TopBottomPanel::bottom("stopped_at").show(ctx, |ui| {
+                ui.label(
+                    egui::RichText::new(format!("🛑 Stopped at {} results.", results)).color(egui::Color32::YELLOW),
+                );
+            });
+        }
+
+        if self.timed_out && !self.is_searching {
+            egui::TopBottomPanel::bottom("timed_out").show(ctx, |ui| {
+                ui.label(
+                    egui::RichText::new("⏱ Time limit reached, these are partial results.")
+                        .color(egui::Color32::YELLOW),
+                );
+            });
+        }
+
+        if let Some((dir_errors, file_errors)) = self.error_summary
+            && !self.is_searching
+        {
+            egui::TopBottomPanel::bottom("error_summary").show(ctx, |ui| {
+                ui.label(
+                    egui::RichText::new(format!(
+                        "⚠ Couldn't read {} folders and {} files, results may be incomplete.",
+                        dir_errors, file_errors
+                    ))
+                    .color(egui::Color32::LIGHT_RED),
+                );
+            });
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some((ref path, skipped)) = self.imported {
+                let mut note = format!("Loaded from file {}", path.display());
+                if skipped > 0 {
+                    note.push_str(&format!(", skipped {} lines that weren't results", skipped));
+                }
+                ui.label(egui::RichText::new(note).color(egui::Color32::GRAY).small());
+                ui.separator();
+            }
+            // what the results on screen were searched with, even if the options have been changed since
+            if self.has_searched && !self.applied_options.is_empty() {
+                ui.add(
+                    egui::Label::new(
+                        egui::RichText::new(format!("Search parameters: {}", self.applied_options.join(" · ")))
+                            .color(egui::Color32::GRAY)
+                            .small(),
+                    )
+                    .wrap(),
+                );
+                ui.separator();
+            }
+
             if self.results.is_empty() {
                 ui.centered_and_justified(|ui| {
                     if self.has_searched && !self.is_searching {
@@ -192,16 +774,107 @@ impl eframe::App for FastSearchApp {
                     }
                 });
             } else {
+                self.refresh_filter();
+                // None shows every result as it came in, otherwise the indexes of the ones that pass
+                // the filter in the order picked
+                let rows = self.filtered.take();
+                let total = rows.as_ref().map_or(self.results.len(), |rows| rows.len());
+                let pages = total.div_ceil(PAGE_SIZE).max(1);
+                self.page = self.page.min(pages - 1);
+
+                ui.horizontal(|ui| {
+                    ui.label("Filter:");
+                    ui.add(egui::TextEdit::singleline(&mut self.results_filter).desired_width(160.0))
+                        .on_hover_text("Only shows results whose path or line contains this");
+                    if ui.button("📋 Copy as Markdown").on_hover_text("A table of path, line and text for pasting into issues").clicked() {
+                        let markdown = match rows {
+                            Some(ref rows) if !self.export_all => results_markdown(rows.iter().map(|&i| self.results.row(i))),
+                            _ => results_markdown(self.results.iter()),
+                        };
+                        ui.ctx().copy_text(markdown);
+                    }
+                    if ui.button("💾 Export").on_hover_text("Saves the results as JSON or CSV").clicked()
+                        && let Some(path) = self.file_dialog()
+                            .add_filter("JSON", &["json"])
+                            .add_filter("CSV", &["csv"])
+                            .set_file_name("results.json")
+                            .save_file()
+                    {
+                        let visible = rows.as_deref().filter(|_| !self.export_all);
+                        self.save_results(path, visible);
+                    }
+                    ui.label("Sort:");
+                    for (mode, label) in [
+                        (SortMode::Found, "as found"),
+                        (SortMode::Path, "path"),
+                        (SortMode::FileName, "file name"),
+                        (SortMode::LineNumber, "line"),
+                        (SortMode::Relevance, "relevance"),
+                    ] {
+                        ui.selectable_value(&mut self.sort_mode, mode, label);
+                    }
+                    if rows.is_some() && !self.results_filter.trim().is_empty() {
+                        ui.label("Export:");
+                        ui.selectable_value(&mut self.export_all, false, "visible");
+                        ui.selectable_value(&mut self.export_all, true, "all");
+                        ui.label(format!("{} of {} results", total, self.results.len()));
+                    } else {
+                        ui.label(format!("{} results", self.results.len()));
+                    }
+                    // one grouping at a time
+                    if ui.checkbox(&mut self.group_by_file, "Group by file").changed() && self.group_by_file {
+                        self.group_by_term = false;
+                    }
+                    if self.applied_terms.len() > 1 && ui.checkbox(&mut self.group_by_term, "Group by term").changed() && self.group_by_term {
+                        self.group_by_file = false;
+                    }
+                    if pages > 1 && !self.group_by_term && !self.group_by_file {
+                        if ui.add_enabled(self.page > 0, egui::Button::new("◀ Prev")).clicked() {
+                            self.page -= 1;
+                        }
+                        ui.label(format!("Page {} of {}", self.page + 1, pages));
+                        if ui.add_enabled(self.page + 1 < pages, egui::Button::new("Next ▶")).clicked() {
+                            self.page += 1;
+                        }
+                    }
+                });
+                ui.separator();
+
+                if self.group_by_term && self.applied_terms.len() > 1 {
+                    self.filtered = rows;
+                    self.term_groups_ui(ui);
+                    return;
+                }
+                if self.group_by_file {
+                    self.filtered = rows;
+                    self.file_groups_ui(ui);
+                    return;
+                }
+
+                // only the current page goes to show_rows, row indexes stay global for expanded_rows
+                let page_start = self.page * PAGE_SIZE;
+                let page_len = total.saturating_sub(page_start).min(PAGE_SIZE);
                 let row_height = ui.text_style_height(&egui::TextStyle::Body);
                 egui::ScrollArea::vertical()
+                    .id_salt(self.page)
                     .auto_shrink([false; 2])
-                    .show_rows(ui, row_height, self.results.len(), |ui, row_range| {
-                        for i in row_range {
-                            if let Some(res) = self.results.get(i) {
-                                self.render_result_row(ui, res);
+                    .show_rows(ui, row_height, page_len, |ui, row_range| {
+                        let mut toggled = Vec::new();
+                        for row in row_range.start + page_start..row_range.end + page_start {
+                            let i = rows.as_ref().map_or(row, |rows| rows[row]);
+                            if i < self.results.len()
+                                && self.render_result_row(ui, &self.results.row(i), self.expanded_rows.contains(&i), false)
+                            {
+                                toggled.push(i);
+                            }
+                        }
+                        for i in toggled {
+                            if !self.expanded_rows.remove(&i) {
+                                self.expanded_rows.insert(i);
                             }
                         }
                     });
+                self.filtered = rows;
             }
         });
     }
@@ -211,6 +884,322 @@ impl eframe::App for FastSearchApp {
 
 
 impl FastSearchApp {
+    // picks the saved settings back up from the last run
+    fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let mut app = Self::default();
+        if let Some(settings) = cc.storage.and_then(|storage| eframe::get_value(storage, eframe::APP_KEY)) {
+            app.settings = settings;
+        }
+        app
+    }
+
+    // opens where the last pick was made, or at the current root before anything has been picked
+    fn file_dialog(&self) -> rfd::FileDialog {
+        let root = std::path::Path::new(&self.root_path);
+        let start = self.settings.last_dir.as_deref()
+            .filter(|dir| dir.is_dir())
+            .or_else(|| if root.is_file() { root.parent() } else { Some(root) }.filter(|dir| dir.is_dir()));
+        match start {
+            Some(dir) => rfd::FileDialog::new().set_directory(dir),
+            None => rfd::FileDialog::new(),
+        }
+    }
+
+    // One chip per bookmarked root, clicking sets it as the root and x forgets it
+    fn bookmarks_ui(&mut self, ui: &mut egui::Ui) {
+        let mut remove = None;
+        ui.horizontal_wrapped(|ui| {
+            for (i, root) in self.settings.bookmarks.iter().enumerate() {
+                let name = std::path::Path::new(root)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| root.clone());
+                if ui.selectable_label(*root == self.root_path, format!("★ {}", name)).on_hover_text(root).clicked() {
+                    self.root_path = root.clone();
+                }
+                if ui.small_button("×").on_hover_text("Remove bookmark").clicked() {
+                    remove = Some(i);
+                }
+            }
+        });
+        if let Some(i) = remove {
+            self.settings.bookmarks.remove(i);
+        }
+    }
+
+    // keeps filtered in step with results_filter and sort_mode. Only results that came in since last
+    // time get filtered, and they're sorted among themselves and merged into the rows already there, so
+    // a running search costs what it just found. A new filter or order starts over. So does every new
+    // result with Relevance, whose scores move whole files, but only the new rows get filtered
+    fn refresh_filter(&mut self) {
+        let needle = self.results_filter.trim().to_lowercase();
+        if needle.is_empty() && self.sort_mode == SortMode::Found {
+            self.filtered = None;
+            return;
+        }
+        let (ref for_needle, seen, for_sort) = self.filtered_for;
+        let (mut rows, seen) = match self.filtered.take() {
+            Some(rows) if *for_needle == needle && for_sort == self.sort_mode && seen <= self.results.len() => (rows, seen),
+            _ => (Vec::new(), 0),
+        };
+        let mut new: Vec<usize> = (seen..self.results.len())
+            .filter(|&i| needle.is_empty() || self.results.contains(i, &needle))
+            .collect();
+        if self.sort_mode == SortMode::Relevance {
+            if !new.is_empty() {
+                rows.append(&mut new);
+                self.sort_mode.sort(&self.results, &mut rows);
+            }
+        } else {
+            self.sort_mode.sort(&self.results, &mut new);
+            merge_rows(&mut rows, new, |a, b| self.sort_mode.cmp(&self.results, a, b));
+        }
+        self.filtered = Some(rows);
+        self.filtered_for = (needle, self.results.len(), self.sort_mode);
+    }
+
+    // keeps term_groups in step with the results and the filter. Like refresh_filter, the rows that
+    // are new since last time get merged into their terms' lists, Relevance sorts them all again
+    fn refresh_groups(&mut self) {
+        let key = (self.results_filter.trim().to_lowercase(), self.sort_mode);
+        let mut groups = match self.term_groups.take() {
+            Some(groups)
+                if self.groups_for == key
+                    && groups.seen <= self.results.len()
+                    && (self.sort_mode != SortMode::Relevance || groups.seen == self.results.len()) =>
+            {
+                groups
+            }
+            _ => TermGroups::default(),
+        };
+        if groups.seen < self.results.len() {
+            let seen = groups.seen;
+            let sort_mode = self.sort_mode;
+            let results = &self.results;
+            match self.filtered {
+                Some(ref rows) => groups.extend(results, rows.iter().copied().filter(|&i| i >= seen), |a, b| sort_mode.cmp(results, a, b)),
+                None => groups.extend(results, seen..results.len(), |a, b| sort_mode.cmp(results, a, b)),
+            }
+        }
+        self.term_groups = Some(groups);
+        self.groups_for = key;
+    }
+
+    // One collapsible section per term with the rows it matched. Each section shows up to PAGE_SIZE
+    // rows, past that the filter has to narrow them down
+    fn term_groups_ui(&mut self, ui: &mut egui::Ui) {
+        self.refresh_groups();
+        let Some(groups) = self.term_groups.take() else {
+            return;
+        };
+        let mut toggled = Vec::new();
+        egui::ScrollArea::vertical().id_salt("term_groups").auto_shrink([false; 2]).show(ui, |ui| {
+            let theme = &self.settings.theme;
+            let sections = groups.by_term.iter().map(|(pattern, rows)| {
+                let term = self.applied_terms.get(*pattern).map_or("?", String::as_str);
+                let title = egui::RichText::new(format!("Matches for \"{}\" ({})", term, rows.len()))
+                    .color(theme.highlight_text)
+                    .background_color(theme.highlight(*pattern));
+                (title, *pattern, rows)
+            });
+            let other = (!groups.other.is_empty())
+                .then(|| (egui::RichText::new(format!("Other results ({})", groups.other.len())), usize::MAX, &groups.other));
+
+            for (title, id, rows) in sections.chain(other) {
+                egui::CollapsingHeader::new(title).id_salt(("term_group", id)).default_open(true).show(ui, |ui| {
+                    for &i in rows.iter().take(PAGE_SIZE) {
+                        if self.render_result_row(ui, &self.results.row(i), self.expanded_rows.contains(&i), false) {
+                            toggled.push(i);
+                        }
+                    }
+                    if rows.len() > PAGE_SIZE {
+                        ui.label(format!("… and {} more, use the filter to narrow them down", rows.len() - PAGE_SIZE));
+                    }
+                });
+            }
+        });
+        for i in toggled {
+            if !self.expanded_rows.remove(&i) {
+                self.expanded_rows.insert(i);
+            }
+        }
+        self.term_groups = Some(groups);
+    }
+
+    // keeps file_groups in step with the results, the filter and the order. Results that came in
+    // since last time just get added on, so the groups fill in while the search runs. A new filter
+    // sorts them all again, and so does every new result with a sort_mode other than Found since
+    // they can land anywhere in the order
+    fn refresh_file_groups(&mut self) {
+        let key = (self.results_filter.trim().to_lowercase(), self.sort_mode);
+        let mut groups = match self.file_groups.take() {
+            Some(groups)
+                if self.file_groups_for == key
+                    && (self.sort_mode == SortMode::Found || groups.seen == self.results.len()) =>
+            {
+                groups
+            }
+            _ => FileGroups::default(),
+        };
+        match self.filtered {
+            Some(ref rows) => {
+                let seen = groups.seen;
+                groups.extend(&self.results, rows.iter().copied().filter(|&i| i >= seen));
+            }
+            None => groups.extend(&self.results, groups.seen..self.results.len()),
+        }
+        self.file_groups = Some(groups);
+        self.file_groups_for = key;
+    }
+
+    // One collapsible entry per file, titled with its path and how many lines matched, closed to
+    // start with. Opening it shows the path once and then every matching line. Up to PAGE_SIZE of
+    // each are shown, like term_groups_ui
+    fn file_groups_ui(&mut self, ui: &mut egui::Ui) {
+        self.refresh_file_groups();
+        let Some(groups) = self.file_groups.take() else {
+            return;
+        };
+        let mut toggled = Vec::new();
+        egui::ScrollArea::vertical().id_salt("file_groups").auto_shrink([false; 2]).show(ui, |ui| {
+            let theme = &self.settings.theme;
+            for group in groups.files.iter().take(PAGE_SIZE) {
+                let mut title = match group.lines {
+                    1 => format!("{} (1 match)", group.path.to_string_lossy()),
+                    n => format!("{} ({} matches)", group.path.to_string_lossy(), n),
+                };
+                if group.name_match {
+                    title.push_str(", name matches");
+                }
+                let color = if group.lines == 0 { theme.file_label } else { theme.path };
+                egui::CollapsingHeader::new(egui::RichText::new(title).color(color))
+                    .id_salt(("file_group", &group.path))
+                    .show(ui, |ui| {
+                        self.path_label(ui, &group.path, theme.text, None);
+                        for &i in group.rows.iter().take(PAGE_SIZE) {
+                            if self.render_result_row(ui, &self.results.row(i), self.expanded_rows.contains(&i), true) {
+                                toggled.push(i);
+                            }
+                        }
+                        if group.rows.len() > PAGE_SIZE {
+                            ui.label(format!("… and {} more, use the filter to narrow them down", group.rows.len() - PAGE_SIZE));
+                        }
+                    });
+            }
+            if groups.files.len() > PAGE_SIZE {
+                ui.label(format!("… and {} more files, use the filter to narrow them down", groups.files.len() - PAGE_SIZE));
+            }
+            if !groups.other.is_empty() {
+                ui.separator();
+                for &i in groups.other.iter().take(PAGE_SIZE) {
+                    self.render_result_row(ui, &self.results.row(i), false, false);
+                }
+            }
+        });
+        for i in toggled {
+            if !self.expanded_rows.remove(&i) {
+                self.expanded_rows.insert(i);
+            }
+        }
+        self.file_groups = Some(groups);
+    }
+
+    fn cancel_search(&mut self) {
+        self.cancel_token.store(true, Ordering::Relaxed);
+        self.is_searching = false;
+    }
+
+    // results from the old root would only mix in with the new one, so a search whose root got
+    // edited (typed, picked, dropped or a bookmark) stops and its results go. Runs at the start of
+    // a frame, which catches a change made in the frame before
+    fn cancel_if_root_changed(&mut self) {
+        if !self.is_searching || self.root_path == self.searched_root {
+            return;
+        }
+        self.cancel_search();
+        self.receiver = None;
+        self.results.clear();
+        self.filtered = None;
+        self.term_groups = None;
+        self.file_groups = None;
+        self.expanded_rows.clear();
+        self.page = 0;
+        self.applied_options.clear();
+        self.has_searched = false;
+    }
+
+    // replaces the results with the ones in a run_search_json/results_file output, a file that can't be
+    // read shows the error where bad patterns go
+    fn import_results(&mut self, path: std::path::PathBuf) {
+        self.pattern_error = None;
+        let loaded = std::fs::File::open(&path)
+            .map_err(SearchError::from)
+            .and_then(|file| load_results_json(std::io::BufReader::new(file)));
+        let (results, skipped) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                self.pattern_error = Some(format!("Couldn't import {}: {}", path.display(), e));
+                return;
+            }
+        };
+
+        self.cancel_search();
+        self.receiver = None;
+        self.results.clear();
+        self.filtered = None;
+        self.term_groups = None;
+        self.file_groups = None;
+        self.expanded_rows.clear();
+        self.page = 0;
+        self.applied_options.clear();
+        self.applied_terms.clear();
+        self.error_summary = None;
+        self.timed_out = false;
+        self.stopped_at = None;
+        self.file_scanned = 0;
+        self.total_entries = None;
+        // the same split the receiver makes while a search runs
+        for result in results {
+            match result {
+                SearchResult::ProgressUpdate(count) => self.file_scanned = self.file_scanned.max(count),
+                SearchResult::ProgressTotal(total) => self.total_entries = Some(total),
+                SearchResult::ErrorSummary { dir_errors, file_errors } => self.error_summary = Some((dir_errors, file_errors)),
+                SearchResult::TimedOut => self.timed_out = true,
+                SearchResult::ResultLimitReached { results } => self.stopped_at = Some(results),
+                result => self.results.push(result),
+            }
+        }
+        self.has_searched = true;
+        self.settings.last_dir = path.parent().map(|p| p.to_path_buf());
+        self.imported = Some((path, skipped));
+    }
+
+    // writes the results (just the rows passing the filter when visible is Some) as CSV when the
+    // file ends in .csv and JSON otherwise, a failed write shows up where bad patterns go
+    fn save_results(&mut self, path: std::path::PathBuf, visible: Option<&[usize]>) {
+        let format = match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => ExportFormat::Csv,
+            _ => ExportFormat::Json,
+        };
+        let picked: Vec<SearchResult>;
+        let results = match visible {
+            Some(rows) => {
+                picked = rows.iter().map(|&i| self.results.row(i).into_owned()).collect();
+                &picked
+            }
+            None => {
+                picked = self.results.iter().map(std::borrow::Cow::into_owned).collect();
+                &picked
+            }
+        };
+        self.pattern_error = std::fs::File::create(&path)
+            .map_err(SearchError::from)
+            .and_then(|file| export_results(results, format, file))
+            .err()
+            .map(|e| format!("Couldn't export to {}: {}", path.display(), e));
+        self.settings.last_dir = path.parent().map(|p| p.to_path_buf());
+    }
+
     fn execute_search(&mut self, ctx: egui::Context) {
 
         self.cancel_token.store(true, Ordering::Relaxed);
@@ -221,12 +1210,60 @@ impl FastSearchApp {
         self.has_searched = true;
         if self.search_term.is_empty() && self.file_name.is_empty() { return; }
 
+        // a bad regex gets caught here so it can be shown next to the field
+        self.pattern_error = None;
+        if self.phonetic
+            && !self.search_everywhere
+            && !self.file_name.trim().is_empty()
+            && !self.file_name.chars().any(|c| c.is_ascii_alphabetic())
+        {
+            self.pattern_error = Some("Sounds like needs a query with letters in it".to_string());
+            return;
+        }
+        if self.regex
+            && let Err(e) = regex::Regex::new(&self.search_term)
+        {
+            self.pattern_error = Some(e.to_string());
+            return;
+        }
+        if self.file_regex
+            && !self.phonetic
+            && !self.search_everywhere
+            && !self.file_name.trim().is_empty()
+            && let Err(e) = regex::Regex::new(&self.file_name)
+        {
+            self.pattern_error = Some(e.to_string());
+            return;
+        }
+        if self.file_glob
+            && !self.file_regex
+            && !self.phonetic
+            && !self.search_everywhere
+            && !self.file_name.trim().is_empty()
+            && let Err(e) = globset::Glob::new(&self.file_name)
+        {
+            self.pattern_error = Some(e.to_string());
+            return;
+        }
+
         if self.root_path.ends_with(":") {
-            self.root_path.push_str("\\");
+            self.root_path.push('\\');
         };
+        self.searched_root = self.root_path.clone();
+        self.imported = None;
 
         
         self.results.clear();
+        self.filtered = None;
+        self.term_groups = None;
+        self.file_groups = None;
+        self.expanded_rows.clear();
+        self.page = 0;
+        self.error_summary = None;
+        self.timed_out = false;
+        self.stopped_at = None;
+        self.file_scanned = 0;
+        self.total_entries = None;
         self.is_searching = true;
         
         let (tx, rx) = mpsc::channel();
@@ -236,85 +1273,1150 @@ impl FastSearchApp {
         .filter(|s| !s.trim().is_empty())
         .cloned();
 
+        let terms: Vec<String> = if self.separate_terms {
+            self.search_term.split_whitespace().map(str::to_string).collect()
+        } else if self.search_term.trim().is_empty() {
+            Vec::new()
+        } else {
+            vec![self.search_term.clone()]
+        };
+        let file_query = if self.search_everywhere { &self.search_term } else { &self.file_name };
         let options = SearchOptions {
             root: self.root_path.clone(),
-            text_query: if self.search_term.trim().is_empty() { None } else { Some(self.search_term.clone()) },
-            file_query: if self.file_name.trim().is_empty() { None } else { Some(self.file_name.clone())},
-            ignore_case: self.ignore_case.clone(),
-            max_depth: self.max_depth.clone(),
+            text_query: terms.first().cloned(),
+            more_text_queries: terms.iter().skip(1).cloned().collect(),
+            match_mode: if self.separate_terms && self.all_terms { MatchMode::All } else { MatchMode::Any },
+            file_query: if file_query.trim().is_empty() { None } else { Some(file_query.clone())},
+            // in search everywhere mode the one query goes in both
+            combine: if self.search_everywhere { Combine::Or } else { Combine::And },
+            ignore_case: false,
+            text_ignore_case: self.text_ignore_case,
+            unicode_case: self.unicode_case,
+            ascii_only: false,
+            flexible_whitespace: self.flexible_whitespace,
+            scope: self.scope,
+            file_ignore_case: self.file_ignore_case,
+            max_depth: self.max_depth,
+            depth_penalty: None,
+            // the GUI orders its results itself, see SortMode::Relevance
+            name_bonus: None,
+            max_entries_per_dir: None,
             file_types: cleaned_file_types,
+            file_regex: self.file_regex && !self.search_everywhere,
+            file_glob: self.file_glob && !self.search_everywhere,
+            phonetic: self.phonetic && !self.search_everywhere,
+            match_full_path: self.match_full_path,
+            error_reporting: self.error_reporting,
+            fixed_string: false,
+            regex: self.regex,
+            replace: None,
+            text_only: self.text_only,
+            checkpoint: None,
+            results_file: None,
+            snapshot: false,
+            threads: None,
+            max_loaded_bytes: None,
+            max_file_size: self.max_file_mb.map(|mb| mb * 1024 * 1024),
+            max_matches_per_file: self.max_matches_per_file,
+            respect_gitignore: self.respect_gitignore,
+            respect_parent_ignores: self.respect_parent_ignores,
+            respect_global_gitignore: self.respect_global_gitignore,
+            git_tracked_only: self.git_tracked_only,
+            git_changed_only: self.git_changed_only,
+            only_gitignored: self.only_gitignored,
+            search_all: self.search_all,
+            exclude_dirs: self.exclude_dirs.split(',').map(str::trim).filter(|name| !name.is_empty()).map(String::from).collect(),
+            dedup_hardlinks: self.dedup_hardlinks,
+            follow_symlinks: self.follow_symlinks,
+            differs_from: None,
+            hidden_only: self.hidden_only,
+            local_only: self.local_only,
+            adaptive_depth: self.adaptive_depth,
+            exclude_paths: Vec::new(),
+            include_hash: false,
+            include_raw_line: false,
+            blame: self.blame,
+            report_timing: self.show_slow_files.then_some(SLOW_FILE),
+            count_total: true,
+            extractors: Extractors::default(),
+            postprocessor: None,
+            alternate_streams: self.alternate_streams,
+            search_gzip: self.search_gzip,
+            max_line_len: self.max_line_len,
+            whole_line: self.whole_line,
+            whole_word: self.whole_word,
+            line_prefix: self.line_prefix,
+            line_prefix_trim: self.line_prefix_trim,
+            section_marker: self.section_marker.clone(),
+            max_duration: self.time_limit.map(std::time::Duration::from_secs),
+            max_results: self.max_results,
+            skip_first_lines: self.skip_first_lines,
+            context: self.context,
+            line_range: self.line_range,
+            numbering: Numbering::OneBased,
+            only_matching: self.only_matching,
+            normalize_unicode: self.normalize_unicode.then_some(Normalization::Nfc),
+            detect_encoding: self.detect_encoding,
         };
+        self.applied_options = options_summary(&options);
+        self.applied_terms = terms;
 
+        let single_file = std::path::Path::new(&self.root_path).is_file();
 
         thread::spawn(move || {
-            run_search(options, tx, thread_token);
+            if single_file {
+                let path = std::path::PathBuf::from(&options.root);
+                let _ = search_file(&path, options, tx, thread_token);
+            } else {
+                let _ = run_search(options, tx, thread_token);
+            }
             ctx.request_repaint(); 
         });
     }
 
-    fn render_result_row(&self, ui: &mut egui::Ui, result: &SearchResult) {
+    // A result's path, clicking opens the file and right clicking its folder. With breadcrumbs on every
+    // folder in it can be clicked, and long paths hide their middle behind a … until it's clicked
+    fn path_label(&self, ui: &mut egui::Ui, path: &std::path::Path, color: egui::Color32, note: Option<&str>) {
+        let note = note.map(|n| format!(" ({})", n)).unwrap_or_default();
+        if !self.breadcrumbs {
+            let response = ui.add(
+                egui::Label::new(egui::RichText::new(format!("{}{}", path.to_string_lossy(), note)).color(color)).wrap(),
+            );
+            if response.clicked() {
+                let _ = open::that(path);
+            }
+            if response.secondary_clicked() {
+                let _ = open::that(path.parent().unwrap_or(path));
+            }
+            return;
+        }
+
+        let mut crumbs: Vec<&std::path::Path> = path.ancestors().filter(|a| !a.as_os_str().is_empty()).collect();
+        crumbs.reverse();
+        let id = ui.make_persistent_id(("breadcrumbs", path));
+        let show_all = ui.data(|d| d.get_temp::<bool>(id)).unwrap_or(false);
+        let hidden = if !show_all && crumbs.len() > MAX_CRUMBS { 2..crumbs.len() - 3 } else { 0..0 };
+
+        ui.horizontal_wrapped(|ui| {
+            ui.spacing_mut().item_spacing.x = 0.0;
+            let mut after_separator = true;
+            for (i, crumb) in crumbs.iter().enumerate() {
+                if hidden.contains(&i) && i != hidden.start {
+                    continue;
+                }
+                if !after_separator {
+                    ui.label(egui::RichText::new(std::path::MAIN_SEPARATOR_STR).color(egui::Color32::GRAY));
+                }
+                if hidden.contains(&i) {
+                    if ui.small_button("…").on_hover_text("Show the whole path").clicked() {
+                        ui.data_mut(|d| d.insert_temp(id, true));
+                    }
+                    after_separator = false;
+                    continue;
+                }
+                let name = crumb.file_name().map(|n| n.to_string_lossy()).unwrap_or_else(|| crumb.to_string_lossy());
+                after_separator = name.ends_with(std::path::MAIN_SEPARATOR);
+
+                let response = ui
+                    .add(egui::Label::new(egui::RichText::new(name).color(color)).sense(egui::Sense::click()))
+                    .on_hover_text(crumb.to_string_lossy());
+                if response.clicked() {
+                    let _ = open::that(crumb);
+                }
+                if i + 1 == crumbs.len() && response.secondary_clicked() {
+                    let _ = open::that(path.parent().unwrap_or(path));
+                }
+            }
+            if !note.is_empty() {
+                ui.label(egui::RichText::new(note).color(color));
+            }
+        });
+    }
+
+    // Draws one result, returns true when the long line expander was clicked. in_group leaves out a
+    // content match's path, the file group it's shown in already has it
+    fn render_result_row(&self, ui: &mut egui::Ui, result: &SearchResult, expanded: bool, in_group: bool) -> bool {
+    let mut toggle_expand = false;
     match result {
-        SearchResult::FileNameMatch { path } => {
+        SearchResult::FileNameMatch { path, note } => {
             ui.vertical(|ui| {
                 ui.label(
                     egui::RichText::new("FILE")
-                        .color(egui::Color32::from_rgb(0, 255, 127))
+                        .color(self.settings.theme.file_label)
                         .strong(),
                 );
 
-                let response = ui.add(
-                    egui::Label::new(
-                        egui::RichText::new(path.to_string_lossy())
-                            .color(egui::Color32::WHITE),
-                    )
-                    .wrap(),
-                );
-
-                if response.clicked() {
-                    let _ = open::that(path);
-                }
-                if response.secondary_clicked() {
-                    let _ = open::that(path.parent().unwrap_or(path));
-                }
+                self.path_label(ui, path, self.settings.theme.text, note.as_deref());
             });
 
             ui.separator();
         }
 
-        SearchResult::ContentMatch { path, line_number, line_text } => {
+        SearchResult::ContentMatch { path, line_number, line_text, truncated, pattern, match_start_col, match_end_col, section, encoding, blame, context_before, context_after, .. } => {
             ui.vertical(|ui| {
-                let response = ui.add(
-                    egui::Label::new(
-                        egui::RichText::new(path.to_string_lossy())
-                            .color(egui::Color32::LIGHT_GRAY),
-                    )
-                    .wrap(), 
-                );
+                if !in_group {
+                    self.path_label(ui, path, self.settings.theme.path, *encoding);
+                } else if let Some(encoding) = encoding {
+                    ui.label(egui::RichText::new(format!("({})", encoding)).color(egui::Color32::GRAY).small());
+                }
 
-                if response.clicked() {
-                    let _ = open::that(path);
+                if let Some(section) = section {
+                    ui.label(egui::RichText::new(format!("in {}", section)).color(egui::Color32::GRAY).italics());
                 }
-                if response.secondary_clicked() {
-                    let _ = open::that(path.parent().unwrap_or(path));
+                if let Some(blame) = blame {
+                    let short = &blame.commit[..blame.commit.len().min(8)];
+                    ui.label(
+                        egui::RichText::new(format!("{} by {}, {}", short, blame.author, time_ago(blame.date)))
+                            .color(egui::Color32::GRAY)
+                            .small(),
+                    );
                 }
 
-                ui.add(
-                    egui::Label::new(
-                        egui::RichText::new(format!(
-                            "{}: {}",
-                            line_number,
-                            line_text.trim()
-                        ))
-                        .color(egui::Color32::WHITE),
-                    )
-                    .wrap(),
-                );
+                // the columns are offsets into the untrimmed line, so shift them by what trim takes off the front
+                let lead = line_text.len() - line_text.trim_start().len();
+                let mut line = std::borrow::Cow::Borrowed(line_text.trim());
+                let mut span = (match_start_col.saturating_sub(lead), match_end_col.saturating_sub(lead));
+
+                // only changes what's drawn, the result (and anything copied from it) keeps the real spacing
+                if self.collapse_whitespace {
+                    let (collapsed, collapsed_span) = collapse_whitespace(&line, span);
+                    line = std::borrow::Cow::Owned(collapsed);
+                    span = collapsed_span;
+                }
+
+                let (shown, cut) = if expanded {
+                    (&*line, false)
+                } else {
+                    truncate_preview(&line, self.preview_chars)
+                };
+
+                // the lines around the match, numbered from it since results always count from 1 here
+                let context_row = |ui: &mut egui::Ui, number: usize, text: &str| {
+                    let (text, _) = truncate_preview(text.trim_end(), self.preview_chars);
+                    ui.label(egui::RichText::new(format!("{}  {}", number, text)).color(egui::Color32::DARK_GRAY));
+                };
+                let first_before = line_number.saturating_sub(context_before.len());
+                for (i, text) in context_before.iter().enumerate() {
+                    context_row(ui, first_before + i, text);
+                }
+
+                let font_id = egui::TextStyle::Body.resolve(ui.style());
+                let theme = &self.settings.theme;
+                let plain = egui::TextFormat::simple(font_id.clone(), theme.text);
+                let mut job = egui::text::LayoutJob::default();
+                job.append(&format!("{}: ", line_number), 0.0, plain.clone());
+
+                // only highlights when the match made it into the preview
+                if span.0 < span.1
+                    && span.1 <= shown.len()
+                    && shown.is_char_boundary(span.0)
+                    && shown.is_char_boundary(span.1)
+                {
+                    job.append(&shown[..span.0], 0.0, plain.clone());
+                    job.append(&shown[span.0..span.1], 0.0, egui::TextFormat {
+                        font_id,
+                        color: theme.highlight_text,
+                        background: theme.highlight(*pattern),
+                        ..Default::default()
+                    });
+                    job.append(&shown[span.1..], 0.0, plain.clone());
+                } else {
+                    job.append(shown, 0.0, plain.clone());
+                }
+                if *truncated && !cut {
+                    job.append(" ⋯", 0.0, plain);
+                }
+
+                ui.horizontal_wrapped(|ui| {
+                    ui.add(egui::Label::new(job).wrap());
+
+                    if cut && ui.link("… show full line").clicked() {
+                        toggle_expand = true;
+                    }
+                    if expanded && ui.link("show less").clicked() {
+                        toggle_expand = true;
+                    }
+                });
+
+                for (i, text) in context_after.iter().enumerate() {
+                    context_row(ui, line_number + 1 + i, text);
+                }
             });
 
             ui.separator();
         }
 
-        SearchResult::ProgressUpdate(_) => {}
+        SearchResult::Error { path, source, message } => {
+            let what = match source {
+                ErrorSource::Directory => "FOLDER",
+                ErrorSource::File => "FILE",
+            };
+            let location = path.as_ref().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+            ui.label(
+                egui::RichText::new(format!("{} ERROR {}: {}", what, location, message))
+                    .color(egui::Color32::LIGHT_RED),
+            );
+            ui.separator();
+        }
+
+        SearchResult::MoreMatches { path, omitted } => {
+            ui.label(
+                egui::RichText::new(format!(
+                    "… and {} more matches in {}",
+                    omitted,
+                    path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default()
+                ))
+                .color(egui::Color32::GRAY)
+                .italics(),
+            );
+            ui.separator();
+        }
+
+        SearchResult::MoreEntries { path, omitted } => {
+            ui.label(
+                egui::RichText::new(format!("… {} more entries in {} were skipped", omitted, path.to_string_lossy()))
+                    .color(egui::Color32::GRAY)
+                    .italics(),
+            );
+            ui.separator();
+        }
+
+        SearchResult::FileTiming { path, micros } => {
+            ui.label(
+                egui::RichText::new(format!("⏱ {} ms to read and scan {}", micros / 1000, path.to_string_lossy()))
+                    .color(egui::Color32::GRAY)
+                    .italics(),
+            );
+            ui.separator();
+        }
+
+        SearchResult::RemoteSkipped { path } => {
+            ui.label(
+                egui::RichText::new(format!("… skipped {}, it isn't on a local drive", path.to_string_lossy()))
+                    .color(egui::Color32::GRAY)
+                    .italics(),
+            );
+            ui.separator();
+        }
+
+        SearchResult::DepthCapped { root, depth } => {
+            ui.label(
+                egui::RichText::new(format!(
+                    "⚠ {} is on a network or removable drive, only searching {} folders deep",
+                    root.to_string_lossy(),
+                    depth
+                ))
+                .color(egui::Color32::YELLOW),
+            );
+            ui.separator();
+        }
+
+        SearchResult::ProgressUpdate(_)
+        | SearchResult::ProgressTotal(_)
+        | SearchResult::ErrorSummary { .. }
+        | SearchResult::TimedOut
+        | SearchResult::ResultLimitReached { .. } => {}
+    }
+    toggle_expand
+}
+}
+
+// Everything the GUI remembers between runs, saved by eframe when the window closes
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Settings {
+    theme: Theme,
+    // where the folder/file picker was last used
+    last_dir: Option<std::path::PathBuf>,
+    // favorite roots, shown above the root box for switching with one click
+    bookmarks: Vec<String>,
+}
+
+// Colors for the result rows
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+struct Theme {
+    file_label: egui::Color32,
+    path: egui::Color32,
+    text: egui::Color32,
+    highlight_text: egui::Color32,
+    // one per search term, picked by pattern index so a term keeps its color
+    highlights: [egui::Color32; 6],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            file_label: egui::Color32::from_rgb(0, 255, 127),
+            path: egui::Color32::LIGHT_GRAY,
+            text: egui::Color32::WHITE,
+            highlight_text: egui::Color32::BLACK,
+            highlights: [
+                egui::Color32::from_rgb(255, 200, 0),
+                egui::Color32::from_rgb(0, 200, 255),
+                egui::Color32::from_rgb(255, 105, 180),
+                egui::Color32::from_rgb(124, 252, 0),
+                egui::Color32::from_rgb(255, 140, 0),
+                egui::Color32::from_rgb(186, 85, 211),
+            ],
+        }
+    }
+}
+
+impl Theme {
+    fn highlight(&self, pattern: usize) -> egui::Color32 {
+        self.highlights[pattern % self.highlights.len()]
+    }
+
+    // the color pickers for the settings window
+    fn edit(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("theme_colors").num_columns(2).show(ui, |ui| {
+            ui.label("File label");
+            ui.color_edit_button_srgba(&mut self.file_label);
+            ui.end_row();
+            ui.label("Path");
+            ui.color_edit_button_srgba(&mut self.path);
+            ui.end_row();
+            ui.label("Text");
+            ui.color_edit_button_srgba(&mut self.text);
+            ui.end_row();
+            ui.label("Highlighted text");
+            ui.color_edit_button_srgba(&mut self.highlight_text);
+            ui.end_row();
+            ui.label("Highlights");
+            ui.horizontal(|ui| {
+                for color in &mut self.highlights {
+                    ui.color_edit_button_srgba(color);
+                }
+            });
+            ui.end_row();
+        });
+        if ui.add_enabled(*self != Theme::default(), egui::Button::new("Reset to defaults")).clicked() {
+            *self = Theme::default();
+        }
+    }
+}
+
+// Short descriptions of everything a search was run with that isn't the default
+fn options_summary(options: &SearchOptions) -> Vec<String> {
+    let mut parts = vec![format!("in {}", options.root)];
+    if let Some(ref text) = options.text_query {
+        let terms: Vec<String> = std::iter::once(text).chain(&options.more_text_queries).map(|t| format!("\"{}\"", t)).collect();
+        let joiner = if options.match_mode == MatchMode::All { " and " } else { " or " };
+        parts.push(format!("text {}", terms.join(joiner)));
+    }
+    if let Some(ref name) = options.file_query {
+        let kind = if options.phonetic {
+            "name sounding like"
+        } else if options.file_regex {
+            "name regex"
+        } else if options.file_glob {
+            "name glob"
+        } else {
+            "name"
+        };
+        let scope = if options.match_full_path { " (full path)" } else { "" };
+        parts.push(format!("{} \"{}\"{}", kind, name, scope));
+    }
+    if let Some(ref types) = options.file_types {
+        parts.push(format!("types {}", types));
+    }
+
+    let flags = [
+        (options.combine == Combine::Or, "names or contents"),
+        (options.ignore_case || options.text_ignore_case, if options.unicode_case { "ignore unicode case in text" } else { "ignore case in text" }),
+        (options.ignore_case || options.file_ignore_case, "ignore case in names"),
+        (options.whole_line, "whole line"),
+        (options.whole_word, "whole words"),
+        (options.regex, "regex"),
+        (options.flexible_whitespace && !options.regex, "flexible whitespace"),
+        (options.scope == MatchScope::CommentsOnly, "comments only"),
+        (options.scope == MatchScope::CodeOnly, "code only"),
+        (options.line_prefix, if options.line_prefix_trim { "line start (after indent)" } else { "line start" }),
+        (options.only_matching, "only matching"),
+        (options.normalize_unicode.is_some(), "unicode normalized"),
+        (options.detect_encoding, "byte order marks decoded"),
+        (options.text_only, "text files only"),
+        (options.search_gzip, "inside .gz"),
+        (options.alternate_streams, "alternate streams"),
+        (options.git_tracked_only, "git tracked only"),
+        (options.git_changed_only, "uncommitted changes only"),
+        (options.only_gitignored, "gitignored only"),
+        (options.hidden_only, "hidden only"),
+        (options.search_all, "system folders too"),
+        (!options.search_all && options.exclude_dirs != DEFAULT_EXCLUDE_DIRS, "own folder skip list"),
+        (options.dedup_hardlinks, "hardlinks once"),
+        (options.follow_symlinks, "following symlinks"),
+        (options.local_only, "local drives only"),
+        (options.adaptive_depth, "shallow on network drives"),
+        (options.report_timing.is_some(), "slow files listed"),
+        (!options.respect_gitignore, "repo ignores off"),
+        (!options.respect_parent_ignores, "parent ignores off"),
+        (!options.respect_global_gitignore, "global ignores off"),
+    ];
+    parts.extend(flags.iter().filter(|(on, _)| *on).map(|(_, label)| label.to_string()));
+
+    if let Some(depth) = options.max_depth {
+        parts.push(format!("depth {}", depth));
+    }
+    if options.skip_first_lines > 0 {
+        parts.push(format!("skip {} lines", options.skip_first_lines));
+    }
+    if options.context > 0 {
+        parts.push(format!("{} lines of context", options.context));
+    }
+    if let Some((first, last)) = options.line_range {
+        parts.push(format!("lines {}-{}", first, last));
+    }
+    if let Some(cap) = options.max_matches_per_file {
+        parts.push(format!("max {} per file", cap));
+    }
+    if let Some(max) = options.max_line_len {
+        parts.push(format!("lines cut at {} bytes", max));
+    }
+    if let Some(max) = options.max_file_size {
+        parts.push(format!("files up to {} MB", max / (1024 * 1024)));
+    }
+    if let Some(limit) = options.max_duration {
+        parts.push(format!("time limit {}s", limit.as_secs()));
+    }
+    if let Some(max) = options.max_results {
+        parts.push(format!("first {} results", max));
+    }
+    if let Some(ref marker) = options.section_marker {
+        parts.push(format!("sections /{}/", marker));
+    }
+    parts
+}
+
+// Paths with more parts than this show the first 2 and last 3 with breadcrumbs on
+const MAX_CRUMBS: usize = 6;
+
+// What counts as slow for Show slow files
+const SLOW_FILE: std::time::Duration = std::time::Duration::from_millis(100);
+
+// What SortMode::Relevance adds to a file whose name and contents both matched, so it goes above
+// files that only matched as often inside
+const NAME_BONUS: f32 = 1.0;
+
+// How the result list is ordered, sorting only ever changes the order rows are shown in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum SortMode {
+    // the order the walker sent them, which changes from run to run
+    #[default]
+    Found,
+    // by full path, a file's lines in order
+    Path,
+    // by just the file name, then like Path
+    FileName,
+    // by line number, then path. File name matches and notes have no line and come first
+    LineNumber,
+    // best files first, scored like SearchOutcome::rank with NAME_BONUS: a hit per matching line,
+    // one for the name, and NAME_BONUS more when both the name and the contents matched. A file's
+    // rows stay together in the order they came, files that score the same stay in found order
+    Relevance,
+}
+
+impl SortMode {
+    // how two rows (indexes into results) compare, results without a path go last with Path and
+    // FileName. Relevance can't tell from two rows, it scores whole files in sort
+    fn cmp(self, results: &ResultStore, a: usize, b: usize) -> std::cmp::Ordering {
+        let path = |i: usize| results.path(i);
+        let line = |i: usize| results.line(i).map_or(0, |(line_number, _)| line_number);
+        match self {
+            SortMode::Found | SortMode::Relevance => std::cmp::Ordering::Equal,
+            SortMode::Path => (path(a).is_none(), path(a), line(a)).cmp(&(path(b).is_none(), path(b), line(b))),
+            SortMode::FileName => {
+                let name = |i| path(i).and_then(|p| p.file_name());
+                (name(a).is_none(), name(a), path(a), line(a)).cmp(&(name(b).is_none(), name(b), path(b), line(b)))
+            }
+            SortMode::LineNumber => (line(a), path(a)).cmp(&(line(b), path(b))),
+        }
+    }
+
+    // sorts rows stably
+    fn sort(self, results: &ResultStore, rows: &mut [usize]) {
+        let path = |i: usize| results.path(i);
+        match self {
+            SortMode::Found => {}
+            SortMode::Path | SortMode::FileName | SortMode::LineNumber => rows.sort_by(|&a, &b| self.cmp(results, a, b)),
+            SortMode::Relevance => {
+                // (hits, name matched, contents matched, first row) per file, over every result so a
+                // filter doesn't change how a file scores
+                let mut files: HashMap<&std::path::Path, (usize, bool, bool, usize)> = HashMap::new();
+                for i in 0..results.len() {
+                    let (path, hits, name) = match results.kind(i) {
+                        RowKind::Line(path) => (path, 1, false),
+                        RowKind::More(path, omitted) => (path, omitted, false),
+                        RowKind::Name(path) => (path, 1, true),
+                        RowKind::Other => continue,
+                    };
+                    let file = files.entry(path).or_insert((0, false, false, i));
+                    file.0 += hits;
+                    file.1 |= name;
+                    file.2 |= !name;
+                }
+                let key = |i: usize| {
+                    let file = path(i).and_then(|p| files.get(p));
+                    let score = file.map_or(f32::NEG_INFINITY, |&(hits, name, contents, _)| {
+                        hits as f32 + if name && contents { NAME_BONUS } else { 0.0 }
+                    });
+                    (score, file.map_or(usize::MAX, |f| f.3))
+                };
+                rows.sort_by(|&a, &b| {
+                    let (a, b) = (key(a), key(b));
+                    b.0.total_cmp(&a.0).then(a.1.cmp(&b.1))
+                });
+            }
+        }
+    }
+}
+
+// the file or folder a result is about, None for ones that aren't about any (summaries, an error
+// without a path)
+fn result_path(result: &SearchResult) -> Option<&std::path::Path> {
+    match result {
+        SearchResult::ContentMatch { path, .. }
+        | SearchResult::FileNameMatch { path, .. }
+        | SearchResult::MoreMatches { path, .. }
+        | SearchResult::MoreEntries { path, .. }
+        | SearchResult::RemoteSkipped { path }
+        | SearchResult::FileTiming { path, .. }
+        | SearchResult::DepthCapped { root: path, .. }
+        | SearchResult::Error { path: Some(path), .. } => Some(path),
+        _ => None,
+    }
+}
+
+// A multi-term search's results sorted by the term that matched them, as indexes into results.
+// A file matching several terms shows up under each, results that aren't a line match (file name
+// matches, notes) go in other
+#[derive(Default)]
+struct TermGroups {
+    by_term: Vec<(usize, Vec<usize>)>,
+    other: Vec<usize>,
+    // results before this one have been sorted in already
+    seen: usize,
+}
+
+impl TermGroups {
+    // sorts in rows, which are all past seen and in cmp's order, and moves seen up to the end of results
+    fn extend(&mut self, results: &ResultStore, rows: impl Iterator<Item = usize>, cmp: impl Fn(usize, usize) -> std::cmp::Ordering) {
+        let mut by_term: std::collections::BTreeMap<usize, Vec<usize>> = std::collections::BTreeMap::new();
+        let mut other = Vec::new();
+        for i in rows {
+            match results.line(i) {
+                Some((_, pattern)) => by_term.entry(pattern).or_default().push(i),
+                None => other.push(i),
+            }
+        }
+        for (pattern, new) in by_term {
+            let at = self.by_term.partition_point(|(p, _)| *p < pattern);
+            if self.by_term.get(at).is_none_or(|(p, _)| *p != pattern) {
+                self.by_term.insert(at, (pattern, Vec::new()));
+            }
+            merge_rows(&mut self.by_term[at].1, new, &cmp);
+        }
+        merge_rows(&mut self.other, other, &cmp);
+        self.seen = results.len();
+    }
+}
+
+// Merges new into rows, both already in cmp's order and everything in new from later results, so
+// rows that compare the same stay in the order they came like a stable sort of the lot would leave
+// them. Each new row's spot is binary searched, the rows in between are copied over as they are
+fn merge_rows(rows: &mut Vec<usize>, new: Vec<usize>, cmp: impl Fn(usize, usize) -> std::cmp::Ordering) {
+    // the usual case while a search runs with no order, or new rows that all go last
+    if rows.last().is_none_or(|&last| new.first().is_none_or(|&first| cmp(last, first).is_le())) {
+        rows.extend(new);
+        return;
+    }
+    let old = std::mem::take(rows);
+    rows.reserve(old.len() + new.len());
+    let mut rest = old.as_slice();
+    for i in new {
+        let at = rest.partition_point(|&row| cmp(row, i).is_le());
+        rows.extend_from_slice(&rest[..at]);
+        rows.push(i);
+        rest = &rest[at..];
+    }
+    rows.extend_from_slice(rest);
+}
+
+// The results sorted under the file they're in, as indexes into results. Files are in the order
+// their first result came in, results that aren't about a file's name or lines (errors, notes about
+// folders) go in other
+#[derive(Default)]
+struct FileGroups {
+    files: Vec<FileGroup>,
+    by_path: HashMap<std::path::PathBuf, usize>,
+    other: Vec<usize>,
+    // results before this one have been sorted in already
+    seen: usize,
+}
+
+struct FileGroup {
+    path: std::path::PathBuf,
+    name_match: bool,
+    // the file's line matches and its MoreMatches note, in the order they came in
+    rows: Vec<usize>,
+    // how many of rows are line matches
+    lines: usize,
+}
+
+impl FileGroups {
+    // sorts in rows, which are all past seen, and moves seen up to the end of results
+    fn extend(&mut self, results: &ResultStore, rows: impl Iterator<Item = usize>) {
+        for i in rows {
+            match results.kind(i) {
+                RowKind::Line(path) => {
+                    let group = self.group(path);
+                    group.rows.push(i);
+                    group.lines += 1;
+                }
+                RowKind::More(path, _) => self.group(path).rows.push(i),
+                RowKind::Name(path) => self.group(path).name_match = true,
+                RowKind::Other => self.other.push(i),
+            }
+        }
+        self.seen = results.len();
+    }
+
+    fn group(&mut self, path: &std::path::Path) -> &mut FileGroup {
+        let files = &mut self.files;
+        let at = *self.by_path.entry(path.to_path_buf()).or_insert_with(|| {
+            files.push(FileGroup { path: path.to_path_buf(), name_match: false, rows: Vec::new(), lines: 0 });
+            files.len() - 1
+        });
+        &mut self.files[at]
+    }
+}
+
+// The results of a search, kept compact so millions of them fit: every path is stored once, the
+// line texts sit back to back in one String, and a line match or file name match with nothing more
+// to it is a small row pointing into those. Ones with more (context, blame, a note...) and the rarer
+// kinds are kept as they came. A SearchResult is only built again for the rows being drawn, so only
+// the current page's strings are ever materialized
+#[derive(Default)]
+struct ResultStore {
+    rows: Vec<StoredRow>,
+    paths: Vec<Arc<std::path::Path>>,
+    path_ids: HashMap<Arc<std::path::Path>, u32>,
+    text: String,
+}
+
+enum StoredRow {
+    // a ContentMatch with just its line, line_text is text[text_start..text_start + text_len]
+    Line { path: u32, line_number: usize, text_start: usize, text_len: u32, pattern: u32, span: (u32, u32), column: u32, truncated: bool },
+    // a FileNameMatch without a note
+    Name { path: u32 },
+    Full(Box<SearchResult>),
+}
+
+// What a row is about, for grouping and scoring rows without building them
+enum RowKind<'a> {
+    Line(&'a std::path::Path),
+    Name(&'a std::path::Path),
+    // a MoreMatches note and how many matches it left out
+    More(&'a std::path::Path, usize),
+    Other,
+}
+
+impl ResultStore {
+    fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    fn clear(&mut self) {
+        *self = ResultStore::default();
+    }
+
+    fn push(&mut self, result: SearchResult) {
+        let row = match result {
+            SearchResult::ContentMatch {
+                path, line_number, line_text, truncated, pattern, match_start_col, match_end_col, column,
+                section: None, hash: None, encoding: None, raw_line, blame: None, context_before, context_after,
+            } if raw_line.is_empty() && context_before.is_empty() && context_after.is_empty() => {
+                let small = (u32::try_from(line_text.len()), u32::try_from(pattern), u32::try_from(match_start_col), u32::try_from(match_end_col), u32::try_from(column));
+                match small {
+                    (Ok(text_len), Ok(pattern), Ok(start), Ok(end), Ok(column)) => {
+                        let text_start = self.text.len();
+                        self.text.push_str(&line_text);
+                        StoredRow::Line { path: self.path_id(path), line_number, text_start, text_len, pattern, span: (start, end), column, truncated }
+                    }
+                    _ => StoredRow::Full(Box::new(SearchResult::ContentMatch {
+                        path, line_number, line_text, truncated, pattern, match_start_col, match_end_col, column,
+                        section: None, hash: None, encoding: None, raw_line, blame: None, context_before, context_after,
+                    })),
+                }
+            }
+            SearchResult::FileNameMatch { path, note: None } => StoredRow::Name { path: self.path_id(path) },
+            result => StoredRow::Full(Box::new(result)),
+        };
+        self.rows.push(row);
+    }
+
+    fn path_id(&mut self, path: std::path::PathBuf) -> u32 {
+        if let Some(&id) = self.path_ids.get(path.as_path()) {
+            return id;
+        }
+        let id = self.paths.len() as u32;
+        let path: Arc<std::path::Path> = path.into();
+        self.paths.push(path.clone());
+        self.path_ids.insert(path, id);
+        id
+    }
+
+    // row i as the search sent it
+    fn row(&self, i: usize) -> std::borrow::Cow<'_, SearchResult> {
+        match self.rows[i] {
+            StoredRow::Line { path, line_number, text_start, text_len, pattern, span, column, truncated } => {
+                std::borrow::Cow::Owned(SearchResult::ContentMatch {
+                    path: self.paths[path as usize].to_path_buf(),
+                    line_number,
+                    line_text: self.text[text_start..text_start + text_len as usize].to_string(),
+                    truncated,
+                    pattern: pattern as usize,
+                    match_start_col: span.0 as usize,
+                    match_end_col: span.1 as usize,
+                    column: column as usize,
+                    section: None,
+                    hash: None,
+                    encoding: None,
+                    raw_line: Vec::new(),
+                    blame: None,
+                    context_before: Vec::new(),
+                    context_after: Vec::new(),
+                })
+            }
+            StoredRow::Name { path } => std::borrow::Cow::Owned(SearchResult::FileNameMatch { path: self.paths[path as usize].to_path_buf(), note: None }),
+            StoredRow::Full(ref result) => std::borrow::Cow::Borrowed(result),
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = std::borrow::Cow<'_, SearchResult>> {
+        (0..self.len()).map(|i| self.row(i))
+    }
+
+    fn kind(&self, i: usize) -> RowKind<'_> {
+        match &self.rows[i] {
+            StoredRow::Line { path, .. } => RowKind::Line(&self.paths[*path as usize]),
+            StoredRow::Name { path } => RowKind::Name(&self.paths[*path as usize]),
+            StoredRow::Full(result) => match result.as_ref() {
+                SearchResult::ContentMatch { path, .. } => RowKind::Line(path),
+                SearchResult::FileNameMatch { path, .. } => RowKind::Name(path),
+                SearchResult::MoreMatches { path, omitted } => RowKind::More(path, *omitted),
+                _ => RowKind::Other,
+            },
+        }
+    }
+
+    // see result_path
+    fn path(&self, i: usize) -> Option<&std::path::Path> {
+        match &self.rows[i] {
+            StoredRow::Line { path, .. } | StoredRow::Name { path } => Some(&self.paths[*path as usize]),
+            StoredRow::Full(result) => result_path(result),
+        }
+    }
+
+    // line_number and pattern of a line match
+    fn line(&self, i: usize) -> Option<(usize, usize)> {
+        match &self.rows[i] {
+            StoredRow::Line { line_number, pattern, .. } => Some((*line_number, *pattern as usize)),
+            StoredRow::Full(result) => match result.as_ref() {
+                SearchResult::ContentMatch { line_number, pattern, .. } => Some((*line_number, *pattern)),
+                _ => None,
+            },
+            StoredRow::Name { .. } => None,
+        }
+    }
+
+    // result_contains for row i
+    fn contains(&self, i: usize, needle: &str) -> bool {
+        let contains = |text: &str| text.to_lowercase().contains(needle);
+        match &self.rows[i] {
+            StoredRow::Line { path, text_start, text_len, .. } => {
+                contains(&self.paths[*path as usize].to_string_lossy())
+                    || contains(&self.text[*text_start..*text_start + *text_len as usize])
+            }
+            StoredRow::Name { path } => contains(&self.paths[*path as usize].to_string_lossy()),
+            StoredRow::Full(result) => result_contains(result, needle),
+        }
+    }
+}
+
+impl FromIterator<SearchResult> for ResultStore {
+    fn from_iter<I: IntoIterator<Item = SearchResult>>(results: I) -> Self {
+        let mut store = ResultStore::default();
+        for result in results {
+            store.push(result);
+        }
+        store
+    }
+}
+
+// "3 days ago" style age of a unix timestamp
+fn time_ago(secs: i64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let days = (now - secs).max(0) / 86_400;
+    match days {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        2..=59 => format!("{} days ago", days),
+        60..=729 => format!("{} months ago", days / 30),
+        _ => format!("{} years ago", days / 365),
+    }
+}
+
+// Results shown per page, the rest stay in ResultStore's compact rows
+const PAGE_SIZE: usize = 10_000;
+
+// The path of the one match when that's all a search found, None for zero or several
+fn single_match(results: &ResultStore) -> Option<&std::path::Path> {
+    let mut matches = (0..results.len()).filter_map(|i| match results.kind(i) {
+        RowKind::Line(path) | RowKind::Name(path) => Some(path),
+        _ => None,
+    });
+    let first = matches.next()?;
+    matches.next().is_none().then_some(first)
+}
+
+// true when the path or the line has needle in it, needle already lowercased
+fn result_contains(result: &SearchResult, needle: &str) -> bool {
+    let contains = |text: &str| text.to_lowercase().contains(needle);
+    match result {
+        SearchResult::ContentMatch { path, line_text, .. } => contains(&path.to_string_lossy()) || contains(line_text),
+        SearchResult::FileNameMatch { path, .. }
+        | SearchResult::MoreMatches { path, .. }
+        | SearchResult::MoreEntries { path, .. }
+        | SearchResult::RemoteSkipped { path }
+        | SearchResult::FileTiming { path, .. }
+        | SearchResult::DepthCapped { root: path, .. } => contains(&path.to_string_lossy()),
+        SearchResult::Error { path, message, .. } => {
+            path.as_ref().is_some_and(|p| contains(&p.to_string_lossy())) || contains(message)
+        }
+        SearchResult::ProgressUpdate(_)
+        | SearchResult::ProgressTotal(_)
+        | SearchResult::ErrorSummary { .. }
+        | SearchResult::TimedOut
+        | SearchResult::ResultLimitReached { .. } => false,
+    }
+}
+
+// The results as a markdown table, errors are left out
+fn results_markdown(results: impl IntoIterator<Item = impl std::ops::Deref<Target = SearchResult>>) -> String {
+    let mut out = String::from("| Path | Line | Text |\n| --- | --- | --- |\n");
+    for result in results {
+        let row = match &*result {
+            SearchResult::FileNameMatch { path, note } => {
+                let note = note.as_ref().map(|n| format!(" ({})", markdown_code(n))).unwrap_or_default();
+                format!("| {} | | *file name match*{} |", markdown_code(&path.to_string_lossy()), note)
+            }
+            SearchResult::ContentMatch { path, line_number, line_text, .. } => format!(
+                "| {} | {} | {} |",
+                markdown_code(&path.to_string_lossy()),
+                line_number,
+                markdown_code(line_text.trim())
+            ),
+            SearchResult::MoreMatches { path, omitted } => {
+                format!("| {} | | *… and {} more matches* |", markdown_code(&path.to_string_lossy()), omitted)
+            }
+            _ => continue,
+        };
+        out.push_str(&row);
+        out.push('\n');
+    }
+    out
+}
+
+// Wraps text in a code span that survives being put in a table cell: the fence is longer than any
+// run of backticks inside it and pipes get escaped so they don't split the cell
+fn markdown_code(text: &str) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    let mut longest = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let fence = "`".repeat(longest + 1);
+    // a space on each side keeps a backtick at either end from merging with the fence
+    let pad = if text.starts_with('`') || text.ends_with('`') { " " } else { "" };
+    format!("{fence}{pad}{}{pad}{fence}", text.replace('|', "\\|"))
+}
+
+// Turns every run of whitespace into a single space and moves the match span to match,
+// offsets past the end of text (only_matching columns) stay past the end
+fn collapse_whitespace(text: &str, span: (usize, usize)) -> (String, (usize, usize)) {
+    let mut out = String::with_capacity(text.len());
+    let mut mapped = (None, None);
+    let mut in_run = false;
+    for (i, c) in text.char_indices() {
+        if i == span.0 {
+            mapped.0 = Some(out.len());
+        }
+        if i == span.1 {
+            mapped.1 = Some(out.len());
+        }
+        if c.is_whitespace() {
+            if !in_run {
+                out.push(' ');
+            }
+            in_run = true;
+        } else {
+            out.push(c);
+            in_run = false;
+        }
+    }
+    let past_end = |offset: usize| out.len() + offset.saturating_sub(text.len());
+    let span = (mapped.0.unwrap_or_else(|| past_end(span.0)), mapped.1.unwrap_or_else(|| past_end(span.1)));
+    (out, span)
+}
+
+// Cuts a line down to at most max_chars characters without splitting a UTF-8 character,
+// the bool says whether anything was cut off
+fn truncate_preview(text: &str, max_chars: usize) -> (&str, bool) {
+    match text.char_indices().nth(max_chars) {
+        Some((idx, _)) => (&text[..idx], true),
+        None => (text, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collapsing_whitespace_keeps_the_highlight_on_the_match() {
+        let (text, span) = collapse_whitespace("a \t b   hit  c", (8, 11));
+        assert_eq!((text.as_str(), span), ("a b hit c", (4, 7)));
+        assert_eq!(&text[span.0..span.1], "hit");
+
+        // a match that runs to the end of the line
+        assert_eq!(collapse_whitespace("x    hit", (5, 8)), ("x hit".to_string(), (2, 5)));
+        // nothing to collapse
+        assert_eq!(collapse_whitespace("é hit", (3, 6)), ("é hit".to_string(), (3, 6)));
+    }
+
+    #[test]
+    fn relevance_puts_files_whose_name_matched_too_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("files");
+        std::fs::create_dir(&root).unwrap();
+        // two hits each without the bonus
+        std::fs::write(root.join("other.txt"), "report\nreport\n").unwrap();
+        std::fs::write(root.join("report.txt"), "report\n").unwrap();
+        std::fs::write(root.join("once.txt"), "report\n").unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        run_search(SearchOptions {
+            root: root.display().to_string(),
+            text_query: Some("report".into()),
+            file_query: Some("report".into()),
+            combine: Combine::Or,
+            ..SearchOptions::default()
+        }, tx, Arc::new(AtomicBool::new(false))).unwrap();
+        let results: ResultStore = rx.into_iter().filter(|r| result_path(r).is_some()).collect();
+
+        let mut rows: Vec<usize> = (0..results.len()).collect();
+        SortMode::Relevance.sort(&results, &mut rows);
+        let names: Vec<_> = rows.iter().map(|&i| results.path(i).unwrap().file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, ["report.txt", "report.txt", "other.txt", "other.txt", "once.txt"]);
+    }
+
+    #[test]
+    fn merging_keeps_ties_in_the_order_they_came() {
+        let by_tens = |a: usize, b: usize| (a / 10).cmp(&(b / 10));
+        let mut rows = vec![10, 11, 30];
+        merge_rows(&mut rows, vec![2, 12, 40], by_tens);
+        assert_eq!(rows, [2, 10, 11, 12, 30, 40]);
+        // with nothing to order by they just go on the end
+        merge_rows(&mut rows, vec![1], |_, _| std::cmp::Ordering::Equal);
+        assert_eq!(rows, [2, 10, 11, 12, 30, 40, 1]);
+    }
+
+    #[test]
+    fn streamed_results_filter_and_sort_like_all_at_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("files");
+        std::fs::create_dir(&root).unwrap();
+        for name in ["b.txt", "a.txt", "c.log", "d.txt"] {
+            std::fs::write(root.join(name), "hit one\nmiss\nhit two\nhit also\n").unwrap();
+        }
+        let (tx, rx) = mpsc::channel();
+        run_search(SearchOptions {
+            root: root.display().to_string(),
+            text_query: Some("hit".into()),
+            more_text_queries: vec!["also".into()],
+            ..SearchOptions::default()
+        }, tx, Arc::new(AtomicBool::new(false))).unwrap();
+        let results: Vec<SearchResult> = rx.into_iter().collect();
+
+        for sort_mode in [SortMode::Found, SortMode::Path, SortMode::FileName, SortMode::LineNumber, SortMode::Relevance] {
+            let mut streamed = FastSearchApp { results_filter: ".txt".into(), sort_mode, ..FastSearchApp::default() };
+            for chunk in results.chunks(3) {
+                chunk.iter().for_each(|result| streamed.results.push(result.clone()));
+                streamed.refresh_filter();
+                streamed.refresh_groups();
+            }
+            let mut at_once = FastSearchApp { results_filter: ".txt".into(), sort_mode, results: results.iter().cloned().collect(), ..FastSearchApp::default() };
+            at_once.refresh_filter();
+            at_once.refresh_groups();
+
+            assert_eq!(streamed.filtered, at_once.filtered, "{:?}", sort_mode);
+            // three .txt files with four matches each, "hit also" matches both terms
+            assert_eq!(streamed.filtered.as_ref().unwrap().len(), 12);
+            let groups = |app: &FastSearchApp| app.term_groups.as_ref().map(|g| (g.by_term.clone(), g.other.clone()));
+            assert_eq!(groups(&streamed), groups(&at_once), "{:?}", sort_mode);
+        }
+    }
+
+    #[test]
+    fn stored_results_come_back_as_they_went_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("files");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(root.join("hit.txt"), "a hit\nmiss\nhit twice, hit\n").unwrap();
+        std::fs::write(root.join("b.txt"), "one more hit\n").unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        run_search(SearchOptions {
+            root: root.display().to_string(),
+            text_query: Some("hit".into()),
+            file_query: Some("hit".into()),
+            combine: Combine::Or,
+            // keeps these as they came
+            context: 1,
+            ..SearchOptions::default()
+        }, tx, Arc::new(AtomicBool::new(false))).unwrap();
+        let (tx, plain) = mpsc::channel();
+        run_search(SearchOptions {
+            root: root.display().to_string(),
+            text_query: Some("hit".into()),
+            file_query: Some("hit".into()),
+            combine: Combine::Or,
+            ..SearchOptions::default()
+        }, tx, Arc::new(AtomicBool::new(false))).unwrap();
+        let sent: Vec<SearchResult> = rx.into_iter().chain(plain).filter(|r| result_path(r).is_some()).collect();
+
+        let store: ResultStore = sent.iter().cloned().collect();
+        assert_eq!(store.len(), sent.len());
+        let compact = store.rows.iter().filter(|row| !matches!(row, StoredRow::Full(_))).count();
+        // both name matches, the plain search's 4 lines and b.txt's line, which has no lines around it
+        assert_eq!(compact, 7);
+        assert_eq!(store.paths.len(), 2);
+        for (i, result) in sent.iter().enumerate() {
+            assert_eq!(format!("{:?}", store.row(i)), format!("{:?}", result));
+            assert_eq!(store.path(i), result_path(result));
+            assert_eq!(store.contains(i, "twice"), result_contains(result, "twice"));
+        }
     }
 }
-}
\ No newline at end of file
@@ -0,0 +1,9 @@
+// SearchResult as MessagePack, for run_search_msgpack
+use crate::{wire, SearchResult};
+
+pub(crate) fn encode(result: &SearchResult) -> Vec<u8> {
+    // paths that aren't valid unicode go out with replacement characters like in the JSON
+    rmp_serde::to_vec(result)
+        .or_else(|_| rmp_serde::to_vec(&wire::lossy_paths(result)))
+        .expect("every field of a SearchResult can be encoded")
+}
@@ -0,0 +1,303 @@
+// Groups the flat result stream into one entry per file, for using the engine as a
+// library without dealing with the channel at all
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{atomic::AtomicBool, mpsc, Arc};
+
+use crate::{run_search, BlameInfo, SearchError, SearchOptions, SearchResult};
+
+// Everything a finished search found, files are in the order they were first reported
+#[derive(Debug, Clone, Default)]
+pub struct SearchOutcome {
+    pub files: Vec<FileResult>,
+    pub dir_errors: usize,
+    pub file_errors: usize,
+    // max_duration ran out, so files is only what was found before that
+    pub timed_out: bool,
+    // max_results was reached, so files only has that many matches in it
+    pub result_limit_reached: bool,
+    // folders cut short by max_entries_per_dir and how many of their entries were skipped
+    pub capped_dirs: Vec<(PathBuf, usize)>,
+    // what local_only left out for being on a network share or removable drive
+    pub remote_skipped: Vec<PathBuf>,
+    // with report_timing, each slow file and how long it took in microseconds
+    pub timings: Vec<(PathBuf, u64)>,
+    // the depth adaptive_depth capped a slow root's walk at
+    pub depth_capped: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileResult {
+    pub path: PathBuf,
+    // the file name matched the file query
+    pub name_match: bool,
+    // FileNameMatch::note, with phonetic
+    pub name_note: Option<String>,
+    pub lines: Vec<LineMatch>,
+    // matches left out by max_matches_per_file
+    pub omitted: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct LineMatch {
+    pub line_number: usize,
+    pub line_text: String,
+    pub truncated: bool,
+    pub pattern: usize,
+    // the match's byte range in line_text, from 0
+    pub match_start_col: usize,
+    pub match_end_col: usize,
+    // where it starts in SearchOptions::numbering
+    pub column: usize,
+    // with section_marker, the nearest section start above the line
+    pub section: Option<String>,
+    // with include_hash
+    pub hash: Option<u64>,
+    // with detect_encoding
+    pub encoding: Option<&'static str>,
+    // with include_raw_line
+    pub raw_line: Vec<u8>,
+    // with blame
+    pub blame: Option<BlameInfo>,
+    // with context, the lines around it
+    pub context_before: Vec<String>,
+    pub context_after: Vec<String>,
+}
+
+impl SearchOutcome {
+    /// Runs the search to completion on this thread and groups what it found:
+    ///
+    /// ```
+    /// # use fast_search::{SearchOptions, SearchOutcome};
+    /// # fn main() -> Result<(), fast_search::SearchError> {
+    /// let outcome = SearchOutcome::search(SearchOptions {
+    ///     root: "src".into(),
+    ///     text_query: Some("TODO".into()),
+    ///     ..Default::default()
+    /// })?;
+    /// for file in &outcome.files {
+    ///     for line in &file.lines {
+    ///         println!("{}:{}: {}", file.path.display(), line.line_number, line.line_text);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search(options: SearchOptions) -> Result<Self, SearchError> {
+        search_blocking(options, |_| {})
+    }
+
+    // Groups results that were already collected (or are still arriving from a channel)
+    pub fn from_results(results: impl IntoIterator<Item = SearchResult>) -> Self {
+        let mut outcome = SearchOutcome::default();
+        let mut index: HashMap<PathBuf, usize> = HashMap::new();
+
+        for result in results {
+            match result {
+                SearchResult::FileNameMatch { path, note } => {
+                    let file = outcome.file_mut(&mut index, path);
+                    file.name_match = true;
+                    file.name_note = note;
+                }
+                SearchResult::ContentMatch { path, line_number, line_text, truncated, pattern, match_start_col, match_end_col, column, section, hash, encoding, raw_line, blame, context_before, context_after } => {
+                    outcome.file_mut(&mut index, path).lines.push(LineMatch {
+                        line_number,
+                        line_text,
+                        truncated,
+                        pattern,
+                        match_start_col,
+                        match_end_col,
+                        column,
+                        section,
+                        hash,
+                        encoding,
+                        raw_line,
+                        blame: blame.map(|b| *b),
+                        context_before,
+                        context_after,
+                    });
+                }
+                SearchResult::MoreMatches { path, omitted } => {
+                    outcome.file_mut(&mut index, path).omitted += omitted;
+                }
+                SearchResult::ErrorSummary { dir_errors, file_errors } => {
+                    outcome.dir_errors += dir_errors;
+                    outcome.file_errors += file_errors;
+                }
+                SearchResult::MoreEntries { path, omitted } => outcome.capped_dirs.push((path, omitted)),
+                SearchResult::RemoteSkipped { path } => outcome.remote_skipped.push(path),
+                SearchResult::FileTiming { path, micros } => outcome.timings.push((path, micros)),
+                SearchResult::DepthCapped { depth, .. } => outcome.depth_capped = Some(depth),
+                SearchResult::TimedOut => outcome.timed_out = true,
+                SearchResult::ResultLimitReached { .. } => outcome.result_limit_reached = true,
+                SearchResult::ProgressUpdate(_) | SearchResult::ProgressTotal(_) | SearchResult::Error { .. } => {}
+            }
+        }
+
+        outcome
+    }
+
+    // Sorts files best first by FileResult::relevance, files that score the same stay in the order
+    // they were found
+    pub fn rank(&mut self, root: &Path, depth_penalty: f32, name_bonus: f32) {
+        let mut scored: Vec<(f32, FileResult)> =
+            self.files.drain(..).map(|file| (file.relevance(root, depth_penalty, name_bonus), file)).collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        self.files = scored.into_iter().map(|(_, file)| file).collect();
+    }
+
+    fn file_mut(&mut self, index: &mut HashMap<PathBuf, usize>, path: PathBuf) -> &mut FileResult {
+        let i = *index.entry(path.clone()).or_insert_with(|| {
+            self.files.push(FileResult { path, name_match: false, name_note: None, lines: Vec::new(), omitted: 0 });
+            self.files.len() - 1
+        });
+        &mut self.files[i]
+    }
+}
+
+impl FileResult {
+    // How many hits the file has (a name match counts as one, plus name_bonus when the contents
+    // matched too), divided by 1 + depth_penalty for every folder between it and root
+    pub fn relevance(&self, root: &Path, depth_penalty: f32, name_bonus: f32) -> f32 {
+        let hits = self.lines.len() + self.omitted + self.name_match as usize;
+        let both = self.name_match && (!self.lines.is_empty() || self.omitted > 0);
+        let score = hits as f32 + if both { name_bonus.max(0.0) } else { 0.0 };
+        let depth = self.path.strip_prefix(root).map_or(0, |rest| rest.components().count().saturating_sub(1));
+        score / (1.0 + depth_penalty.max(0.0)).powi(depth as i32)
+    }
+}
+
+// Runs a whole search and hands back the grouped results, progress gets the number of entries
+// visited so far every time the walker reports some. The walk runs on its own thread while this
+// one collects, so progress comes in while it's still going
+pub fn search_blocking(options: SearchOptions, mut progress: impl FnMut(usize)) -> Result<SearchOutcome, SearchError> {
+    let ranking = (options.depth_penalty.is_some() || options.name_bonus.is_some()).then(|| {
+        (PathBuf::from(&options.root), options.depth_penalty.unwrap_or(0.0), options.name_bonus.unwrap_or(0.0))
+    });
+    let (tx, rx) = mpsc::channel();
+    std::thread::scope(|scope| {
+        let walker = scope.spawn(move || run_search(options, tx, Arc::new(AtomicBool::new(false))));
+
+        let mut scanned = 0;
+        let mut outcome = SearchOutcome::from_results(rx.iter().inspect(|result| {
+            if let SearchResult::ProgressUpdate(count) = result
+                && *count > scanned
+            {
+                scanned = *count;
+                progress(scanned);
+            }
+        }));
+
+        walker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+        if let Some((root, penalty, bonus)) = ranking {
+            outcome.rank(&root, penalty, bonus);
+        }
+        Ok(outcome)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(path: &str) -> SearchResult {
+        SearchResult::ContentMatch {
+            path: PathBuf::from(path),
+            line_number: 1,
+            line_text: "hit".into(),
+            truncated: false,
+            pattern: 0,
+            match_start_col: 0,
+            match_end_col: 3,
+            column: 1,
+            section: None,
+            hash: None,
+            encoding: None,
+            raw_line: Vec::new(),
+            blame: None,
+            context_before: Vec::new(),
+            context_after: Vec::new(),
+        }
+    }
+
+    fn order(outcome: &SearchOutcome) -> Vec<&Path> {
+        outcome.files.iter().map(|f| f.path.as_path()).collect()
+    }
+
+    #[test]
+    fn depth_penalty_puts_shallow_matches_first() {
+        let mut outcome = SearchOutcome::from_results([hit("root/a/b/deep.txt"), hit("root/a/mid.txt"), hit("root/top.txt")]);
+        outcome.rank(Path::new("root"), 0.5, 0.0);
+        assert_eq!(order(&outcome), [Path::new("root/top.txt"), Path::new("root/a/mid.txt"), Path::new("root/a/b/deep.txt")]);
+    }
+
+    #[test]
+    fn more_hits_can_outrank_depth() {
+        let mut outcome = SearchOutcome::from_results([hit("root/a/deep.txt"), hit("root/a/deep.txt"), hit("root/a/deep.txt"), hit("root/top.txt")]);
+        outcome.rank(Path::new("root"), 0.5, 0.0);
+        assert_eq!(order(&outcome), [Path::new("root/a/deep.txt"), Path::new("root/top.txt")]);
+    }
+
+    #[test]
+    fn search_ranks_with_depth_penalty() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        for name in ["a/b/same.txt", "same.txt", "a/same.txt"] {
+            std::fs::write(dir.path().join(name), "hit\n").unwrap();
+        }
+
+        let outcome = SearchOutcome::search(SearchOptions {
+            root: dir.path().display().to_string(),
+            text_query: Some("hit".into()),
+            depth_penalty: Some(1.0),
+            ..SearchOptions::default()
+        }).unwrap();
+
+        let files: Vec<_> = outcome.files.iter().map(|f| f.path.strip_prefix(dir.path()).unwrap()).collect();
+        assert_eq!(files, [Path::new("same.txt"), Path::new("a/same.txt"), Path::new("a/b/same.txt")]);
+    }
+
+    #[test]
+    fn name_bonus_breaks_ties_for_name_matches() {
+        let name = SearchResult::FileNameMatch { path: PathBuf::from("root/report.txt"), note: None };
+        let results = [hit("root/other.txt"), hit("root/other.txt"), hit("root/report.txt"), name];
+
+        let mut outcome = SearchOutcome::from_results(results.clone());
+        outcome.rank(Path::new("root"), 0.0, 0.0);
+        assert_eq!(order(&outcome), [Path::new("root/other.txt"), Path::new("root/report.txt")]);
+
+        let mut outcome = SearchOutcome::from_results(results);
+        outcome.rank(Path::new("root"), 0.0, 1.0);
+        assert_eq!(order(&outcome), [Path::new("root/report.txt"), Path::new("root/other.txt")]);
+    }
+
+    #[test]
+    fn search_ranks_name_matches_first() {
+        let dir = tempfile::tempdir().unwrap();
+        // two hits each without the bonus: report.txt's line plus its name, other.txt's two lines
+        std::fs::write(dir.path().join("other.txt"), "report\nreport\n").unwrap();
+        std::fs::write(dir.path().join("report.txt"), "report\n").unwrap();
+
+        let search = |name_bonus| SearchOutcome::search(SearchOptions {
+            root: dir.path().display().to_string(),
+            file_query: Some("report".into()),
+            text_query: Some("report".into()),
+            combine: crate::Combine::Or,
+            name_bonus: Some(name_bonus),
+            ..SearchOptions::default()
+        }).unwrap();
+        let scores = |outcome: &SearchOutcome, bonus| {
+            let mut files: Vec<_> = outcome.files.iter()
+                .map(|f| (f.path.strip_prefix(dir.path()).unwrap().to_path_buf(), f.relevance(dir.path(), 0.0, bonus)))
+                .collect();
+            files.sort_by(|a, b| a.0.cmp(&b.0));
+            files.into_iter().map(|(_, score)| score).collect::<Vec<_>>()
+        };
+
+        assert_eq!(scores(&search(0.0), 0.0), [2.0, 2.0]);
+        let ranked = search(1.0);
+        assert_eq!(scores(&ranked, 1.0), [2.0, 3.0]);
+        let files: Vec<_> = ranked.files.iter().map(|f| f.path.strip_prefix(dir.path()).unwrap()).collect();
+        assert_eq!(files, [Path::new("report.txt"), Path::new("other.txt")]);
+    }
+}
@@ -0,0 +1,77 @@
+// Soundex codes for SearchOptions::phonetic, so "Stephen" finds "Steven". Only ASCII letters count,
+// a word without any has no code
+const CODE_LEN: usize = 4;
+
+// "Robert" -> "R163": the first letter, then the digits of the consonants that follow, with
+// neighbours that sound alike (and ones only split by h/w) written once, padded to four
+pub(crate) fn soundex(word: &str) -> Option<String> {
+    let mut letters = word.chars().filter(char::is_ascii_alphabetic).map(|c| c.to_ascii_lowercase());
+    let first = letters.next()?;
+    let mut code = String::from(first.to_ascii_uppercase());
+    let mut last = digit(first);
+
+    for c in letters {
+        let d = digit(c);
+        if let Some(d) = d
+            && last != Some(d)
+        {
+            code.push(d);
+            if code.len() == CODE_LEN {
+                break;
+            }
+        }
+        // a vowel in between means the same sound twice gets written twice, h and w don't
+        if !matches!(c, 'h' | 'w') {
+            last = d;
+        }
+    }
+    while code.len() < CODE_LEN {
+        code.push('0');
+    }
+    Some(code)
+}
+
+fn digit(c: char) -> Option<char> {
+    match c {
+        'b' | 'f' | 'p' | 'v' => Some('1'),
+        'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+        'd' | 't' => Some('3'),
+        'l' => Some('4'),
+        'm' | 'n' => Some('5'),
+        'r' => Some('6'),
+        _ => None,
+    }
+}
+
+// the words of a name or query with their codes, split at anything that isn't a letter
+pub(crate) fn word_codes(text: &str) -> Vec<(&str, String)> {
+    text.split(|c: char| !c.is_alphabetic())
+        .filter_map(|word| soundex(word).map(|code| (word, code)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_codes() {
+        for (word, code) in [("Robert", "R163"), ("Rupert", "R163"), ("Ashcraft", "A261"), ("Tymczak", "T522"), ("Pfister", "P236"), ("Lee", "L000")] {
+            assert_eq!(soundex(word).as_deref(), Some(code), "{}", word);
+        }
+        assert_eq!(soundex("42"), None);
+    }
+
+    #[test]
+    fn sound_alikes_share_a_code() {
+        assert_eq!(soundex("Stephen"), soundex("Steven"));
+        assert_eq!(soundex("Smith"), soundex("Smyth"));
+        assert_ne!(soundex("Stephen"), soundex("Robert"));
+    }
+
+    #[test]
+    fn words_of_a_file_name() {
+        let codes: Vec<_> = word_codes("steven_invoice-2024.pdf").into_iter().map(|(word, _)| word).collect();
+        assert_eq!(codes, ["steven", "invoice", "pdf"]);
+    }
+}
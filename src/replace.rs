@@ -0,0 +1,348 @@
+// Find and replace on top of the search: the lines a search reports get text_query swapped for a
+// replacement, with $1/${name} filled in from the match when regex is on
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use regex::bytes::{Captures, Regex};
+
+use crate::{is_whole_word, Numbering, SearchError, SearchOptions, SearchOutcome, TextMatcher};
+
+// Everything that changed (or would change, with dry_run) in one file
+#[derive(Debug, Clone)]
+pub struct FileReplacement {
+    pub path: PathBuf,
+    // how many matches got replaced, a line can have several
+    pub replacements: usize,
+    pub lines: Vec<ReplacedLine>,
+}
+
+// One line before and after the replace, for previewing it
+#[derive(Debug, Clone)]
+pub struct ReplacedLine {
+    pub line_number: usize,
+    pub before: String,
+    pub after: String,
+}
+
+// Searches with options and puts options.replace in place of text_query everywhere it matched, nothing
+// happens without one. Only the files the search matched get touched (so file_types, text_only and
+// binary detection apply the same), and in them only the lines it reported, so whole_line, scope,
+// line_range and the rest narrow down what gets replaced too (whole_word is checked for each match on
+// the line as well), and a match has to sit on one line. With regex the replacement can use $1 or
+// ${name} for the match's groups ($$ for a plain $), otherwise it's put in as is.
+//
+// Each file is read into memory, changed there and written to a temporary file next to it that's
+// renamed over it, so a file is either changed completely or not at all. An error stops there, files
+// before it keep their changes. dry_run writes nothing and only reports what would change.
+// more_text_queries, files inside .gz and ones read through an extractor are left alone.
+//
+// A $ reference that doesn't name one of the pattern's groups (like $1x, which is group "1x" and not
+// $1 then x) refuses to run with UnclearReplacement before anything is searched, rather than quietly
+// putting nothing there. confirm_unclear runs it anyway, read the way the regex crate reads it
+pub fn apply_replacements(mut options: SearchOptions, dry_run: bool, confirm_unclear: bool) -> Result<Vec<FileReplacement>, SearchError> {
+    let Some(replacement) = options.replace.take() else {
+        return Ok(Vec::new());
+    };
+    let replacement = replacement.as_str();
+
+    // only text_query gets replaced: every other term would get the same replacement, and in regex
+    // mode each term sits in a group of its own that would move $1 onto the wrong group
+    options.more_text_queries.clear();
+
+    // the search's own matcher, so a line gets replaced exactly where the search found it (the same
+    // case folding, the same engine)
+    let matcher = TextMatcher::new(&options)?.ok_or(SearchError::PatternError)?;
+    let expand = options.regex && !options.fixed_string;
+    if expand
+        && !confirm_unclear
+        && let TextMatcher::Regex { ref re, .. } = matcher
+        && let Some(reference) = unknown_group(re, replacement)
+    {
+        return Err(SearchError::UnclearReplacement(reference));
+    }
+
+    let substitution = Substitution { matcher, replacement, expand, whole_word: options.whole_word };
+
+    let numbering = options.numbering;
+    let search_gzip = options.search_gzip;
+    let extractors = options.extractors.clone();
+    let outcome = SearchOutcome::search(options)?;
+
+    let mut changed = Vec::new();
+    for file in &outcome.files {
+        let gzip = search_gzip && file.path.extension().is_some_and(|e| e.eq_ignore_ascii_case("gz"));
+        if file.lines.is_empty() || gzip || extractors.get(&file.path).is_some() {
+            continue;
+        }
+        let lines: HashSet<usize> = file.lines.iter().map(|l| l.line_number).collect();
+        if let Some(replaced) = replace_lines(&file.path, &lines, numbering, &substitution, dry_run)? {
+            changed.push(replaced);
+        }
+    }
+    Ok(changed)
+}
+
+// apply_replacements with replacement as options.replace, refusing an unclear one
+pub fn run_replace(options: SearchOptions, replacement: &str, dry_run: bool) -> Result<Vec<FileReplacement>, SearchError> {
+    apply_replacements(SearchOptions { replace: Some(replacement.to_string()), ..options }, dry_run, false)
+}
+
+// the first $name or ${name} in replacement that isn't a group of re, the same way regex reads them:
+// an unbraced name takes every letter, digit and _ after the $
+fn unknown_group(re: &Regex, replacement: &str) -> Option<String> {
+    let mut rest = replacement;
+    while let Some(at) = rest.find('$') {
+        rest = &rest[at + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            rest = after;
+            continue;
+        }
+        let (reference, name) = match rest.strip_prefix('{').and_then(|r| r.split_once('}')) {
+            Some((name, after)) => {
+                let reference = &rest[..name.len() + 2];
+                rest = after;
+                (reference, name)
+            }
+            None => {
+                let len = rest.find(|c: char| c != '_' && !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+                let (name, after) = rest.split_at(len);
+                rest = after;
+                (name, name)
+            }
+        };
+        // a $ with no name after it is kept as it is
+        if name.is_empty() {
+            continue;
+        }
+        let known = match name.parse::<usize>() {
+            Ok(index) => index < re.captures_len(),
+            Err(_) => re.capture_names().flatten().any(|group| group == name),
+        };
+        if !known {
+            return Some(format!("${}", reference));
+        }
+    }
+    None
+}
+
+// What gets swapped for what, the same for every line
+struct Substitution<'a> {
+    matcher: TextMatcher,
+    replacement: &'a str,
+    // fills in $1/${name}, otherwise the replacement goes in as is. Only ever on with a regex matcher
+    expand: bool,
+    // leaves matches that are part of a longer word alone
+    whole_word: bool,
+}
+
+impl Substitution<'_> {
+    // the line with every match replaced and how many there were
+    fn apply(&self, text: &[u8]) -> (Vec<u8>, usize) {
+        let mut new = Vec::with_capacity(text.len());
+        let mut copied = 0;
+        let mut count = 0;
+        let matches: Box<dyn Iterator<Item = (std::ops::Range<usize>, Option<Captures>)>> = match self.matcher {
+            TextMatcher::Regex { ref re, .. } if self.expand => {
+                Box::new(re.captures_iter(text).map(|caps| (caps.get_match().range(), Some(caps))))
+            }
+            ref matcher => Box::new(matcher.find_iter(text, 0).map(|(_, range)| (range, None))),
+        };
+        for (found, caps) in matches {
+            if self.whole_word && !is_whole_word(text, found.start, found.end) {
+                continue;
+            }
+            new.extend_from_slice(&text[copied..found.start]);
+            match caps {
+                Some(caps) => caps.expand(self.replacement.as_bytes(), &mut new),
+                None => new.extend_from_slice(self.replacement.as_bytes()),
+            }
+            copied = found.end;
+            count += 1;
+        }
+        new.extend_from_slice(&text[copied..]);
+        (new, count)
+    }
+}
+
+fn replace_lines(
+    path: &Path,
+    lines: &HashSet<usize>,
+    numbering: Numbering,
+    substitution: &Substitution,
+    dry_run: bool,
+) -> Result<Option<FileReplacement>, SearchError> {
+    let before = fs::read(path)?;
+    let mut after = Vec::with_capacity(before.len());
+    let mut replaced = FileReplacement { path: path.to_path_buf(), replacements: 0, lines: Vec::new() };
+
+    for (i, line) in before.split_inclusive(|&b| b == b'\n').enumerate() {
+        let line_number = numbering.line(i + 1);
+        // the line ending stays as it was
+        let ending = if line.ends_with(b"\r\n") { 2 } else if line.ends_with(b"\n") { 1 } else { 0 };
+        let (text, ending) = line.split_at(line.len() - ending);
+        let (new, count) = if lines.contains(&line_number) { substitution.apply(text) } else { (Vec::new(), 0) };
+        if count == 0 {
+            after.extend_from_slice(line);
+            continue;
+        }
+
+        replaced.replacements += count;
+        replaced.lines.push(ReplacedLine {
+            line_number,
+            before: String::from_utf8_lossy(text).into_owned(),
+            after: String::from_utf8_lossy(&new).into_owned(),
+        });
+        after.extend_from_slice(&new);
+        after.extend_from_slice(ending);
+    }
+
+    if replaced.replacements == 0 {
+        return Ok(None);
+    }
+    if !dry_run && after != before {
+        write_atomically(path, &after)?;
+    }
+    Ok(Some(replaced))
+}
+
+// writes next to the file and renames over it, a symlink gets its target changed rather than
+// being replaced by a plain file
+fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let path = fs::canonicalize(path)?;
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let temp = path.with_file_name(format!(".{}.fast_search_tmp", name));
+
+    let written = (|| {
+        let mut file = File::create(&temp)?;
+        file.write_all(bytes)?;
+        file.set_permissions(fs::metadata(&path)?.permissions())?;
+        file.sync_all()
+    })();
+    match written.and_then(|_| fs::rename(&temp, &path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(root: &Path, query: &str) -> SearchOptions {
+        SearchOptions { root: root.display().to_string(), text_query: Some(query.to_string()), ..SearchOptions::default() }
+    }
+
+    #[test]
+    fn renames_with_backreferences() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        fs::write(&file, "fn parse_old() {}\nfn render_old(x: u8) {}\nlet parse_old = 1;\n").unwrap();
+
+        let changed = run_replace(SearchOptions { regex: true, ..options(dir.path(), r"fn (\w+)_old") }, "fn ${1}_new", false).unwrap();
+
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].replacements, 2);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "fn parse_new() {}\nfn render_new(x: u8) {}\nlet parse_old = 1;\n");
+    }
+
+    #[test]
+    fn dry_run_only_previews() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        fs::write(&file, "fn parse_old() {}\r\nother\r\n").unwrap();
+
+        let changed = run_replace(SearchOptions { regex: true, ..options(dir.path(), r"fn (\w+)_old") }, "fn ${1}_new", true).unwrap();
+
+        let lines = &changed[0].lines;
+        assert_eq!(lines.len(), 1);
+        assert_eq!((lines[0].line_number, lines[0].before.as_str(), lines[0].after.as_str()), (1, "fn parse_old() {}", "fn parse_new() {}"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "fn parse_old() {}\r\nother\r\n");
+    }
+
+    #[test]
+    fn literal_replacement_ignores_dollars() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "price: cost\n").unwrap();
+
+        run_replace(options(dir.path(), "cost"), "$1", false).unwrap();
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "price: $1\n");
+    }
+
+    // an ASCII query ignoring case only folds ASCII in the search, so the Kelvin sign it didn't find
+    // mustn't get replaced either
+    #[test]
+    fn folds_case_like_the_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "k K \u{212A}\n").unwrap();
+
+        run_replace(SearchOptions { text_ignore_case: true, ..options(dir.path(), "k") }, "x", false).unwrap();
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "x x \u{212A}\n");
+    }
+
+    #[test]
+    fn rewrites_matched_files_only() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.rs"), "let oldName = oldName + 1;\n").unwrap();
+        fs::write(dir.path().join("b.rs"), "nothing here\n").unwrap();
+        fs::write(dir.path().join("c.md"), "oldName\n").unwrap();
+
+        let options = SearchOptions { replace: Some("newName".into()), file_types: Some("rs".into()), ..options(dir.path(), "oldName") };
+        let changed = apply_replacements(options, false, false).unwrap();
+
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].replacements, 2);
+        assert_eq!(fs::read_to_string(dir.path().join("a.rs")).unwrap(), "let newName = newName + 1;\n");
+        assert_eq!(fs::read_to_string(dir.path().join("b.rs")).unwrap(), "nothing here\n");
+        // left out by file_types like the search
+        assert_eq!(fs::read_to_string(dir.path().join("c.md")).unwrap(), "oldName\n");
+    }
+
+    #[test]
+    fn leaves_binary_files_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = b"\x7fELF\0\0\x01oldName\0\x02\x03".to_vec();
+        fs::write(dir.path().join("tool.bin"), &binary).unwrap();
+        fs::write(dir.path().join("a.txt"), "oldName\n").unwrap();
+
+        let options = SearchOptions { replace: Some("newName".into()), ..options(dir.path(), "oldName") };
+        let changed = apply_replacements(options, false, false).unwrap();
+
+        assert_eq!(changed.len(), 1);
+        assert_eq!(fs::read(dir.path().join("tool.bin")).unwrap(), binary);
+        assert_eq!(fs::read_to_string(dir.path().join("a.txt")).unwrap(), "newName\n");
+    }
+
+    #[test]
+    fn nothing_without_a_replacement() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "oldName\n").unwrap();
+
+        assert!(apply_replacements(options(dir.path(), "oldName"), false, false).unwrap().is_empty());
+        assert_eq!(fs::read_to_string(dir.path().join("a.txt")).unwrap(), "oldName\n");
+    }
+
+    #[test]
+    fn refuses_an_unclear_group_reference_unless_confirmed() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "fn a_old\n").unwrap();
+        let options = || SearchOptions { regex: true, replace: Some("$1x".into()), ..options(dir.path(), r"(\w+)_old") };
+
+        let refused = apply_replacements(options(), false, false);
+        assert!(matches!(refused, Err(SearchError::UnclearReplacement(ref r)) if r == "$1x"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "fn a_old\n");
+
+        // $1x is the group named 1x, which isn't there, so the match turns into nothing
+        apply_replacements(options(), false, true).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "fn \n");
+    }
+}
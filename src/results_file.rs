@@ -0,0 +1,210 @@
+// Appends results to SearchOptions::results_file as JSON Lines while the search runs, for long batch
+// jobs that shouldn't lose everything to a crash. Lines pile up in memory and get written in one go
+// once there's enough of them or a second has passed, so the file only ever ends on a whole line.
+// Without the json feature there's no results_file and Recorded just passes everything on
+#[cfg(feature = "json")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "json")]
+use std::io::{self, Read, Seek, SeekFrom, Write};
+#[cfg(feature = "json")]
+use std::path::Path;
+#[cfg(feature = "json")]
+use std::sync::Mutex;
+#[cfg(feature = "json")]
+use std::time::{Duration, Instant};
+
+use crate::{SearchError, SearchOptions, Sink};
+#[cfg(feature = "json")]
+use crate::{wire, LineHit, SearchResult};
+
+#[cfg(not(feature = "json"))]
+pub(crate) struct Recorded<'a, S>(&'a S);
+
+#[cfg(not(feature = "json"))]
+impl<'a, S: Sink> Recorded<'a, S> {
+    pub(crate) fn open(_options: &SearchOptions, inner: &'a S) -> Result<Self, SearchError> {
+        Ok(Recorded(inner))
+    }
+
+    pub(crate) fn finish(self) -> Result<(), SearchError> {
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "json"))]
+impl<S: Sink> Sink for Recorded<'_, S> {
+    fn emit(&self, result: crate::SearchResult) {
+        self.0.emit(result);
+    }
+
+    fn content_match(&self, path: &std::path::Path, hit: crate::LineHit<'_>) {
+        self.0.content_match(path, hit);
+    }
+}
+
+#[cfg(feature = "json")]
+const WRITE_EVERY: Duration = Duration::from_secs(1);
+#[cfg(feature = "json")]
+const WRITE_AT_BYTES: usize = 64 * 1024;
+
+#[cfg(feature = "json")]
+struct Pending {
+    file: File,
+    lines: Vec<u8>,
+    written_at: Instant,
+    // the first write that failed, the rest of the results aren't written after it
+    failed: Option<io::Error>,
+}
+
+#[cfg(feature = "json")]
+impl Pending {
+    fn write_out(&mut self) {
+        if self.failed.is_none()
+            && let Err(e) = self.file.write_all(&self.lines)
+        {
+            self.failed = Some(e);
+        }
+        self.lines.clear();
+        self.written_at = Instant::now();
+    }
+}
+
+// Passes everything on to inner and keeps a copy in the file, without a file it's just inner
+#[cfg(feature = "json")]
+pub(crate) struct Recorded<'a, S> {
+    inner: &'a S,
+    pending: Option<Mutex<Pending>>,
+}
+
+#[cfg(feature = "json")]
+impl<'a, S: Sink> Recorded<'a, S> {
+    pub(crate) fn open(options: &SearchOptions, inner: &'a S) -> Result<Self, SearchError> {
+        let pending = options.results_file.as_deref()
+            .map(|path| -> io::Result<_> {
+                let mut file = OpenOptions::new().create(true).read(true).append(true).open(path)?;
+                drop_partial_line(&mut file)?;
+                Ok(Mutex::new(Pending { file, lines: Vec::new(), written_at: Instant::now(), failed: None }))
+            })
+            .transpose()?;
+        Ok(Recorded { inner, pending })
+    }
+
+    // writes what's left, a write that failed during the search comes back here
+    pub(crate) fn finish(self) -> Result<(), SearchError> {
+        let Some(pending) = self.pending else {
+            return Ok(());
+        };
+        let mut pending = pending.into_inner().unwrap();
+        pending.write_out();
+        match pending.failed {
+            Some(e) => Err(SearchError::Io(e)),
+            None => Ok(pending.file.sync_data()?),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl<S: Sink> Sink for Recorded<'_, S> {
+    fn emit(&self, result: SearchResult) {
+        if let Some(ref pending) = self.pending {
+            let mut pending = pending.lock().unwrap();
+            // progress comes in steadily, so it's what makes a quiet stretch still get written out
+            if !matches!(result, SearchResult::ProgressUpdate(_)) {
+                pending.lines.extend_from_slice(wire::to_json_line(&result).as_bytes());
+            }
+            if !pending.lines.is_empty()
+                && (pending.lines.len() >= WRITE_AT_BYTES || pending.written_at.elapsed() >= WRITE_EVERY)
+            {
+                pending.write_out();
+            }
+        }
+        self.inner.emit(result);
+    }
+
+    fn content_match(&self, path: &Path, hit: LineHit<'_>) {
+        match self.pending {
+            Some(_) => self.emit(crate::content_result(path, hit)),
+            None => self.inner.content_match(path, hit),
+        }
+    }
+}
+
+// a run killed in the middle of a write can leave the start of a line at the end, that gets cut off
+// so the next run's lines don't get glued onto it
+#[cfg(feature = "json")]
+fn drop_partial_line(file: &mut File) -> io::Result<()> {
+    let len = file.metadata()?.len();
+    // only the tail needs looking at, the rest is whole lines
+    let mut tail_start = len;
+    loop {
+        if tail_start == 0 {
+            return file.set_len(0);
+        }
+        let from = tail_start.saturating_sub(WRITE_AT_BYTES as u64);
+        let mut chunk = vec![0; (tail_start - from) as usize];
+        file.seek(SeekFrom::Start(from))?;
+        file.read_exact(&mut chunk)?;
+        if let Some(end) = memchr::memrchr(b'\n', &chunk) {
+            let keep = from + end as u64 + 1;
+            return if keep < len { file.set_len(keep) } else { Ok(()) };
+        }
+        tail_start = from;
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+    use crate::{load_results_json, run_search};
+    use std::sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}, mpsc};
+
+    // whatever a kill at this point would leave behind has to load back without losing a line
+    #[test]
+    fn the_file_is_whole_lines_mid_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = dir.path().join("tree");
+        std::fs::create_dir(&tree).unwrap();
+        std::fs::write(tree.join("a.txt"), "a line with a hit on it\n".repeat(5000)).unwrap();
+        let results = dir.path().join("results.jsonl");
+
+        let seen = AtomicUsize::new(0);
+        let checked = Arc::new(AtomicUsize::new(0));
+        let options = SearchOptions {
+            root: tree.display().to_string(),
+            text_query: Some("hit".into()),
+            results_file: Some(results.clone()),
+            ..SearchOptions::default()
+        }
+        .with_postprocessor({
+            let (results, checked) = (results.clone(), checked.clone());
+            move |result| {
+                if seen.fetch_add(1, Ordering::Relaxed) % 1000 == 999 {
+                    let (loaded, skipped) = load_results_json(std::io::BufReader::new(File::open(&results).unwrap())).unwrap();
+                    assert_eq!(skipped, 0);
+                    checked.fetch_max(loaded.len(), Ordering::Relaxed);
+                }
+                Some(result)
+            }
+        });
+        let (tx, _rx) = mpsc::channel();
+        run_search(options, tx, Arc::new(AtomicBool::new(false))).unwrap();
+
+        // some of it was on disk before the search was done
+        assert!(checked.load(Ordering::Relaxed) > 0);
+        let (loaded, skipped) = load_results_json(std::io::BufReader::new(File::open(&results).unwrap())).unwrap();
+        assert_eq!((loaded.len(), skipped), (5000, 0));
+    }
+
+    #[test]
+    fn a_cut_off_line_is_dropped_before_appending() {
+        let dir = tempfile::tempdir().unwrap();
+        let results = dir.path().join("results.jsonl");
+        std::fs::write(&results, "{\"type\":\"timed_out\"}\n{\"type\":\"content_ma").unwrap();
+        let options = SearchOptions { results_file: Some(results.clone()), ..SearchOptions::default() };
+
+        let (tx, _rx) = mpsc::channel::<SearchResult>();
+        Recorded::open(&options, &tx).unwrap().finish().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&results).unwrap(), "{\"type\":\"timed_out\"}\n");
+    }
+}
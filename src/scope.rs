@@ -0,0 +1,139 @@
+// Finds where the comments are in a source file, for MatchScope. A rough lexer: it knows line and
+// block comments and steps over string literals so a "//" inside quotes isn't taken for one, but
+// nothing fancier (raw strings, nested block comments, heredocs...)
+use std::ops::Range;
+
+// Which part of a source file content matches count in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchScope {
+    #[default]
+    Anywhere,
+    // only matches inside comments, e.g. for TODOs
+    CommentsOnly,
+    // only matches outside comments
+    CodeOnly,
+}
+
+// How one family of languages writes comments and strings
+struct Syntax {
+    line: &'static [u8],
+    block: Option<(&'static [u8], &'static [u8])>,
+    // ' starts a string (python, js...) instead of a char literal or a rust lifetime
+    single_quote_strings: bool,
+}
+
+const C_LIKE: Syntax = Syntax { line: b"//", block: Some((b"/*", b"*/")), single_quote_strings: false };
+const JS_LIKE: Syntax = Syntax { line: b"//", block: Some((b"/*", b"*/")), single_quote_strings: true };
+const HASH: Syntax = Syntax { line: b"#", block: None, single_quote_strings: true };
+
+// The languages MatchScope understands, by extension. Files in anything else count as all code
+fn syntax_for(ext: &str) -> Option<&'static Syntax> {
+    match ext.to_ascii_lowercase().as_str() {
+        "rs" | "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "java" | "go" | "cs" | "swift" | "kt" | "kts"
+        | "scala" | "dart" => Some(&C_LIKE),
+        "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx" | "php" => Some(&JS_LIKE),
+        "py" | "sh" | "bash" | "zsh" | "rb" | "pl" | "r" | "yaml" | "yml" | "toml" | "ps1" => Some(&HASH),
+        _ => None,
+    }
+}
+
+// Byte ranges of every comment in the file, in order. Empty for languages it doesn't know
+pub(crate) fn comment_ranges(ext: &str, bytes: &[u8]) -> Vec<Range<usize>> {
+    let Some(syntax) = syntax_for(ext) else {
+        return Vec::new();
+    };
+    let mut comments = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        if rest.starts_with(syntax.line) {
+            let end = memchr::memchr(b'\n', rest).map_or(bytes.len(), |p| i + p);
+            comments.push(i..end);
+            i = end;
+        } else if let Some((open, close)) = syntax.block
+            && rest.starts_with(open)
+        {
+            let end = memchr::memmem::find(&rest[open.len()..], close).map_or(bytes.len(), |p| i + open.len() + p + close.len());
+            comments.push(i..end);
+            i = end;
+        } else if rest[0] == b'"' || (rest[0] == b'\'' && syntax.single_quote_strings) {
+            i = string_end(bytes, i);
+        } else if rest[0] == b'\'' {
+            i = char_end(bytes, i);
+        } else {
+            i += 1;
+        }
+    }
+    comments
+}
+
+// true when offset sits inside one of the (sorted) comment ranges
+pub(crate) fn in_comment(comments: &[Range<usize>], offset: usize) -> bool {
+    let after = comments.partition_point(|c| c.start <= offset);
+    after > 0 && comments[after - 1].contains(&offset)
+}
+
+// just past the quote that closes the string opened at start, backslash escapes skipped.
+// An unclosed string runs to the end of the line
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\n' => return i,
+            b if b == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+// 'x' and '\n' are skipped as char literals, anything else ('a in a rust lifetime) is just the quote
+fn char_end(bytes: &[u8], start: usize) -> usize {
+    match bytes.get(start + 1..) {
+        Some([b'\\', ..]) => string_end(bytes, start),
+        Some(rest) => {
+            // the character can be several bytes of UTF-8 before the closing quote
+            let width = rest.first().map_or(1, |&b| match b {
+                0xF0.. => 4,
+                0xE0.. => 3,
+                0xC0.. => 2,
+                _ => 1,
+            });
+            if rest.get(width) == Some(&b'\'') { start + width + 2 } else { start + 1 }
+        }
+        None => start + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "// todo: parse it\nfn todo<'a>(s: &'a str) -> char {\n    let url = \"http://todo\";\n    /* todo\n       block */\n    let c = '\"'; // todo after a quote\n    todo!()\n}\n";
+
+    fn comments<'a>(ext: &str, text: &'a str) -> Vec<&'a str> {
+        comment_ranges(ext, text.as_bytes()).into_iter().map(|r| &text[r]).collect()
+    }
+
+    #[test]
+    fn rust_comments() {
+        assert_eq!(comments("rs", FIXTURE), ["// todo: parse it", "/* todo\n       block */", "// todo after a quote"]);
+    }
+
+    #[test]
+    fn hash_comments_and_quotes() {
+        assert_eq!(comments("py", "x = '#not' # real\n"), ["# real"]);
+        // unknown languages are all code
+        assert!(comments("txt", "// not a comment here").is_empty());
+    }
+
+    #[test]
+    fn offsets_in_comments() {
+        let ranges = [2..5, 9..12];
+        let inside: Vec<_> = (0..13).filter(|&i| in_comment(&ranges, i)).collect();
+        assert_eq!(inside, [2, 3, 4, 9, 10, 11]);
+    }
+}
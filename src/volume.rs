@@ -0,0 +1,142 @@
+// Tells network shares and removable drives apart from local disks, for local_only. Best effort:
+// linux reads the mount table, windows asks for the drive type, anywhere else everything is local
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+
+// filesystems that live on another machine, fuse ones show up as fuse.<name>
+#[cfg(target_os = "linux")]
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "ncpfs", "afs", "9p", "ceph", "glusterfs", "lustre",
+    "fuse.sshfs", "fuse.rclone", "fuse.davfs2", "davfs",
+];
+
+#[cfg(target_os = "linux")]
+pub(crate) struct Volumes {
+    // every mount point and whether it's remote/removable, longest first so the first match is the closest
+    mounts: Vec<(PathBuf, bool)>,
+}
+
+#[cfg(target_os = "linux")]
+impl Volumes {
+    pub(crate) fn load() -> Self {
+        let mut mounts: Vec<(PathBuf, bool)> = std::fs::read_to_string("/proc/self/mountinfo")
+            .unwrap_or_default()
+            .lines()
+            .filter_map(parse_mount)
+            .collect();
+        mounts.sort_by_key(|(point, _)| std::cmp::Reverse(point.as_os_str().len()));
+        Volumes { mounts }
+    }
+
+    // path has to be absolute
+    pub(crate) fn is_slow(&self, path: &Path) -> bool {
+        self.mounts.iter().find(|(point, _)| path.starts_with(point)).is_some_and(|&(_, slow)| slow)
+    }
+}
+
+// "36 35 98:0 /root /mnt/point rw,noatime master:1 - ext4 /dev/sda1 rw", the optional fields
+// before the "-" can be any length
+#[cfg(target_os = "linux")]
+fn parse_mount(line: &str) -> Option<(PathBuf, bool)> {
+    let mut fields = line.split(' ');
+    let device = fields.nth(2)?;
+    let point = fields.nth(1)?;
+    let fs_type = fields.skip_while(|&f| f != "-").nth(1)?;
+    let slow = NETWORK_FILESYSTEMS.contains(&fs_type) || is_removable(device);
+    Some((PathBuf::from(unescape_mount(point)), slow))
+}
+
+// the kernel flags removable disks in sysfs, a partition's flag is on the disk it belongs to
+#[cfg(target_os = "linux")]
+fn is_removable(device: &str) -> bool {
+    let Ok(dir) = std::fs::canonicalize(format!("/sys/dev/block/{}", device)) else {
+        return false;
+    };
+    [dir.join("removable"), dir.join("../removable")]
+        .iter()
+        .find_map(|flag| std::fs::read_to_string(flag).ok())
+        .is_some_and(|flag| flag.trim() == "1")
+}
+
+// spaces, tabs, newlines and backslashes in mount points come through as \040 style octal escapes
+#[cfg(target_os = "linux")]
+fn unescape_mount(point: &str) -> String {
+    let bytes = point.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes.get(i + 1..i + 4).and_then(|d| std::str::from_utf8(d).ok()).and_then(|d| u8::from_str_radix(d, 8).ok());
+        match octal {
+            Some(b) if bytes[i] == b'\\' => {
+                out.push(b);
+                i += 4;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(windows)]
+pub(crate) struct Volumes;
+
+#[cfg(windows)]
+impl Volumes {
+    pub(crate) fn load() -> Self {
+        Volumes
+    }
+
+    // the drive (or \\server\share) the path is on has to be a fixed local one
+    pub(crate) fn is_slow(&self, path: &Path) -> bool {
+        use std::os::windows::ffi::OsStrExt;
+        use windows_sys::Win32::Storage::FileSystem::{GetDriveTypeW, GetVolumePathNameW};
+        use windows_sys::Win32::System::WindowsProgramming::{DRIVE_REMOTE, DRIVE_REMOVABLE};
+
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut root = [0u16; 261];
+        if unsafe { GetVolumePathNameW(wide.as_ptr(), root.as_mut_ptr(), root.len() as u32) } == 0 {
+            return false;
+        }
+        matches!(unsafe { GetDriveTypeW(root.as_ptr()) }, DRIVE_REMOTE | DRIVE_REMOVABLE)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub(crate) struct Volumes;
+
+#[cfg(not(any(target_os = "linux", windows)))]
+impl Volumes {
+    pub(crate) fn load() -> Self {
+        Volumes
+    }
+
+    pub(crate) fn is_slow(&self, _path: &Path) -> bool {
+        false
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mount_lines() {
+        let nfs = "50 30 0:45 / /mnt/share rw,relatime shared:7 - nfs4 server:/export rw";
+        let local = "36 35 98:0 /root /home/my\\040files rw,noatime master:1 - ext4 /dev/sda1 rw";
+        assert_eq!(parse_mount(nfs), Some((PathBuf::from("/mnt/share"), true)));
+        assert_eq!(parse_mount(local), Some((PathBuf::from("/home/my files"), false)));
+        assert_eq!(parse_mount("garbage"), None);
+    }
+
+    #[test]
+    fn closest_mount_decides() {
+        let volumes = Volumes { mounts: vec![(PathBuf::from("/mnt/share/local"), false), (PathBuf::from("/mnt/share"), true), (PathBuf::from("/"), false)] };
+        assert!(volumes.is_slow(Path::new("/mnt/share/docs")));
+        assert!(!volumes.is_slow(Path::new("/mnt/share/local/docs")));
+        assert!(!volumes.is_slow(Path::new("/home")));
+    }
+}
@@ -0,0 +1,73 @@
+// What the serde derives on SearchResult need a hand with, for run_search_json, results_file,
+// load_results_json and run_search_msgpack. A result goes out as one object tagged with its kind,
+// e.g. {"type":"content_match","path":"src/main.rs","line_number":3,...}
+use serde::{Deserialize, Deserializer};
+
+// ContentMatch::encoding is a &'static str, so reading one back maps the name onto the ones decode_bom gives out
+pub(crate) fn encoding_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<&'static str>, D::Error> {
+    let name = Option::<String>::deserialize(deserializer)?;
+    Ok(name.and_then(|name| crate::encoding_named(&name)))
+}
+
+// ProgressUpdate and ProgressTotal hold a bare number, which has nowhere to go next to the "type"
+// tag, so it goes out as {"type":"progress","count":50}
+pub(crate) mod count {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Count {
+        count: usize,
+    }
+
+    pub(crate) fn serialize<S: Serializer>(count: &usize, serializer: S) -> Result<S::Ok, S::Error> {
+        Count { count: *count }.serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+        Count::deserialize(deserializer).map(|c| c.count)
+    }
+}
+
+// serde refuses paths that aren't valid unicode, this is the result with theirs turned into
+// replacement characters
+#[cfg(any(feature = "json", feature = "msgpack"))]
+pub(crate) fn lossy_paths(result: &crate::SearchResult) -> crate::SearchResult {
+    use std::path::{Path, PathBuf};
+    use crate::SearchResult;
+
+    let lossy = |path: &Path| PathBuf::from(path.to_string_lossy().into_owned());
+    let mut result = result.clone();
+    match &mut result {
+        SearchResult::ContentMatch { path, .. }
+        | SearchResult::FileNameMatch { path, .. }
+        | SearchResult::MoreMatches { path, .. }
+        | SearchResult::MoreEntries { path, .. }
+        | SearchResult::RemoteSkipped { path }
+        | SearchResult::FileTiming { path, .. }
+        | SearchResult::DepthCapped { root: path, .. }
+        | SearchResult::Error { path: Some(path), .. } => *path = lossy(path),
+        _ => {}
+    }
+    result
+}
+
+// One result as a line of JSON, newline included
+#[cfg(feature = "json")]
+pub(crate) fn to_json_line(result: &crate::SearchResult) -> String {
+    let mut line = serde_json::to_string(result)
+        .or_else(|_| serde_json::to_string(&lossy_paths(result)))
+        .expect("every field of a SearchResult can be written as JSON");
+    line.push('\n');
+    line
+}
+
+// One row of export_results, line_number and line_text are null for a file name match
+#[cfg(feature = "json")]
+#[derive(serde::Serialize)]
+pub(crate) struct ExportRow<'a> {
+    #[serde(rename = "type")]
+    pub(crate) kind: &'a str,
+    pub(crate) path: std::borrow::Cow<'a, str>,
+    pub(crate) line_number: Option<usize>,
+    pub(crate) line_text: Option<&'a str>,
+}